@@ -0,0 +1,276 @@
+//! Bitcoin leg of a Vara exchange.
+//!
+//! Builds the P2WSH HTLC locked by the same SHA-256 hashlock and timelock as
+//! an [`Exchange`], together with the witnesses that spend it:
+//!
+//! ```text
+//! OP_IF
+//!     OP_SIZE 32 OP_EQUALVERIFY OP_SHA256 <hashlock> OP_EQUALVERIFY <receiver>
+//! OP_ELSE
+//!     <timelock> OP_CHECKLOCKTIMEVERIFY OP_DROP <sender>
+//! OP_ENDIF
+//! OP_CHECKSIG
+//! ```
+//!
+//! The size check keeps the Bitcoin side to the 32-byte secrets the Vara
+//! program accepts, so a secret revealed on one chain always redeems the other.
+
+use bitcoin::{
+    absolute::LockTime,
+    ecdsa::Signature,
+    opcodes::all::{
+        OP_CHECKSIG, OP_CLTV, OP_DROP, OP_ELSE, OP_ENDIF, OP_EQUALVERIFY, OP_IF, OP_SHA256, OP_SIZE,
+    },
+    script::Builder,
+    Address, Network, PublicKey, ScriptBuf, Witness,
+};
+use exchange_io::Exchange;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The timelock does not fit a Bitcoin timestamp lock time.
+    InvalidTimelock(u64),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidTimelock(timelock) => {
+                write!(f, "timelock {timelock} is not a valid Bitcoin lock time")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Htlc {
+    pub hashlock: [u8; 32],
+    /// Milliseconds since the Unix epoch, as in the Vara `timelock`.
+    pub timelock: u64,
+    /// Redeems with the secret.
+    pub receiver: PublicKey,
+    /// Refunds after the timelock.
+    pub sender: PublicKey,
+}
+
+impl Htlc {
+    pub fn new(hashlock: [u8; 32], timelock: u64, receiver: PublicKey, sender: PublicKey) -> Self {
+        Htlc {
+            hashlock,
+            timelock,
+            receiver,
+            sender,
+        }
+    }
+
+    /// Bitcoin leg sharing the hashlock and timelock of a Vara exchange.
+    pub fn from_exchange(exchange: &Exchange, receiver: PublicKey, sender: PublicKey) -> Self {
+        Htlc::new(exchange.hashlock, exchange.timelock, receiver, sender)
+    }
+
+    /// The timelock in whole seconds, rounded up so the Bitcoin refund never
+    /// opens before the Vara one.
+    pub fn lock_time(&self) -> Result<LockTime, Error> {
+        u32::try_from(self.timelock.div_ceil(1000))
+            .ok()
+            .and_then(|seconds| LockTime::from_time(seconds).ok())
+            .ok_or(Error::InvalidTimelock(self.timelock))
+    }
+
+    pub fn witness_script(&self) -> Result<ScriptBuf, Error> {
+        Ok(Builder::new()
+            .push_opcode(OP_IF)
+            .push_opcode(OP_SIZE)
+            .push_int(32)
+            .push_opcode(OP_EQUALVERIFY)
+            .push_opcode(OP_SHA256)
+            .push_slice(self.hashlock)
+            .push_opcode(OP_EQUALVERIFY)
+            .push_key(&self.receiver)
+            .push_opcode(OP_ELSE)
+            .push_lock_time(self.lock_time()?)
+            .push_opcode(OP_CLTV)
+            .push_opcode(OP_DROP)
+            .push_key(&self.sender)
+            .push_opcode(OP_ENDIF)
+            .push_opcode(OP_CHECKSIG)
+            .into_script())
+    }
+
+    pub fn script_pubkey(&self) -> Result<ScriptBuf, Error> {
+        Ok(ScriptBuf::new_p2wsh(&self.witness_script()?.wscript_hash()))
+    }
+
+    pub fn address(&self, network: Network) -> Result<Address, Error> {
+        Ok(Address::p2wsh(&self.witness_script()?, network))
+    }
+
+    /// Witness spending the HTLC through the hashlock branch. `signature` is
+    /// the receiver's signature over the spending transaction.
+    pub fn redeem_witness(
+        &self,
+        signature: &Signature,
+        secret: [u8; 32],
+    ) -> Result<Witness, Error> {
+        let mut witness = Witness::new();
+        witness.push_ecdsa_signature(signature);
+        witness.push(secret);
+        witness.push([1u8]);
+        witness.push(self.witness_script()?);
+        Ok(witness)
+    }
+
+    /// Witness spending the HTLC through the timelock branch. `signature` is
+    /// the sender's signature over a transaction whose lock time is at least
+    /// [`Htlc::lock_time`] and whose input sequence is not final.
+    pub fn refund_witness(&self, signature: &Signature) -> Result<Witness, Error> {
+        let mut witness = Witness::new();
+        witness.push_ecdsa_signature(signature);
+        witness.push([]);
+        witness.push(self.witness_script()?);
+        Ok(witness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::{sha256, Hash};
+
+    /// Compressed public keys of the secret keys 1 and 2.
+    const RECEIVER: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const SENDER: &str = "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+    /// SHA-256 of 32 zero bytes.
+    const HASHLOCK: &str = "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925";
+    /// 2023-11-14T22:13:20Z, in milliseconds.
+    const TIMELOCK: u64 = 1_700_000_000_000;
+
+    /// The script of the module docs for the values above, built by hand.
+    const WITNESS_SCRIPT: &str = concat!(
+        "63",
+        "82",
+        "0120",
+        "88",
+        "a8",
+        "20",
+        "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925",
+        "88",
+        "21",
+        "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        "67",
+        "0400f15365",
+        "b1",
+        "75",
+        "21",
+        "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+        "68",
+        "ac",
+    );
+
+    /// DER signature with `SIGHASH_ALL`.
+    const SIGNATURE: &str = "304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee01";
+
+    fn htlc() -> Htlc {
+        Htlc::new(
+            hex(HASHLOCK).try_into().unwrap(),
+            TIMELOCK,
+            RECEIVER.parse().unwrap(),
+            SENDER.parse().unwrap(),
+        )
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn signature() -> Signature {
+        Signature::from_slice(&hex(SIGNATURE)).unwrap()
+    }
+
+    #[test]
+    fn hashlock_vector() {
+        assert_eq!(
+            sha256::Hash::hash(&[0; 32]).to_byte_array().to_vec(),
+            hex(HASHLOCK)
+        );
+    }
+
+    #[test]
+    fn witness_script() {
+        assert_eq!(
+            htlc().witness_script().unwrap().to_bytes(),
+            hex(WITNESS_SCRIPT)
+        );
+    }
+
+    #[test]
+    fn script_pubkey() {
+        assert_eq!(
+            htlc().script_pubkey().unwrap().to_bytes(),
+            hex("0020a7e852117044862fa95f6a25529c8b66ba67660602c4ead5caf89b6e8051d1f0")
+        );
+    }
+
+    #[test]
+    fn address() {
+        let htlc = htlc();
+
+        assert_eq!(
+            htlc.address(Network::Regtest).unwrap().to_string(),
+            "bcrt1q5l59yytsgjrzl22ldgj498ytv6axwesxqtzw44w2lzdkaqz368cqns3rv6"
+        );
+        assert_eq!(
+            htlc.address(Network::Testnet).unwrap().to_string(),
+            "tb1q5l59yytsgjrzl22ldgj498ytv6axwesxqtzw44w2lzdkaqz368cq7fm9eq"
+        );
+        assert_eq!(
+            htlc.address(Network::Bitcoin).unwrap().to_string(),
+            "bc1q5l59yytsgjrzl22ldgj498ytv6axwesxqtzw44w2lzdkaqz368cqfpd2r0"
+        );
+    }
+
+    #[test]
+    fn redeem_witness() {
+        let witness = htlc().redeem_witness(&signature(), [0; 32]).unwrap();
+
+        assert_eq!(
+            witness.to_vec(),
+            [hex(SIGNATURE), vec![0; 32], vec![1], hex(WITNESS_SCRIPT)]
+        );
+    }
+
+    #[test]
+    fn refund_witness() {
+        let witness = htlc().refund_witness(&signature()).unwrap();
+
+        assert_eq!(
+            witness.to_vec(),
+            [hex(SIGNATURE), vec![], hex(WITNESS_SCRIPT)]
+        );
+    }
+
+    #[test]
+    fn lock_time_rounds_up_to_seconds() {
+        let mut htlc = htlc();
+        assert_eq!(
+            htlc.lock_time().unwrap(),
+            LockTime::from_time(1_700_000_000).unwrap()
+        );
+
+        htlc.timelock += 1;
+        assert_eq!(
+            htlc.lock_time().unwrap(),
+            LockTime::from_time(1_700_000_001).unwrap()
+        );
+
+        // Below 500_000_000 a lock time is a block height.
+        htlc.timelock = 1_000;
+        assert_eq!(htlc.lock_time(), Err(Error::InvalidTimelock(1_000)));
+    }
+}
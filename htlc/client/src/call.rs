@@ -0,0 +1,172 @@
+use crate::Secret;
+use exchange_io::{
+    ExchangeAction, ExchangeCompliance, ExchangeLimits, FundOptions, RedeemSignature,
+};
+use gstd::ActorId;
+
+/// A message to the exchange program together with the value it carries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    pub action: ExchangeAction,
+    pub value: u128,
+}
+
+impl Call {
+    fn new(action: ExchangeAction) -> Self {
+        Call { action, value: 0 }
+    }
+
+    pub fn fund(receiver: ActorId, hashlock: [u8; 32], timelock: u64) -> FundBuilder {
+        FundBuilder::new(receiver, hashlock, timelock)
+    }
+
+    pub fn redeem(exchange_id: [u8; 32], secret: Secret) -> Self {
+        Call::new(ExchangeAction::Redeem(exchange_id, secret.0))
+    }
+
+    /// Relayed redeem. `signature` is the receiver's signature over
+    /// [`redeem_payload`](exchange_io::redeem_payload) of the program and the
+    /// same arguments, as wallets sign it.
+    pub fn redeem_with_signature(
+        exchange_id: [u8; 32],
+        secret: Secret,
+        payout: ActorId,
+        signature: RedeemSignature,
+    ) -> Self {
+        Call::new(ExchangeAction::RedeemWithSignature(
+            exchange_id,
+            secret.0,
+            payout,
+            signature,
+        ))
+    }
+
+    pub fn refund(exchange_id: [u8; 32]) -> Self {
+        Call::new(ExchangeAction::Refund(exchange_id))
+    }
+
+    /// Early refund with the preimage of the exchange's refund hashlock.
+    pub fn refund_with_secret(exchange_id: [u8; 32], refund_secret: Secret) -> Self {
+        Call::new(ExchangeAction::RefundWithSecret(
+            exchange_id,
+            refund_secret.0,
+        ))
+    }
+
+    pub fn claim(exchange_id: [u8; 32]) -> Self {
+        Call::new(ExchangeAction::Claim(exchange_id))
+    }
+
+    pub fn dispute(exchange_id: [u8; 32]) -> Self {
+        Call::new(ExchangeAction::Dispute(exchange_id))
+    }
+
+    pub fn clawback(exchange_id: [u8; 32]) -> Self {
+        Call::new(ExchangeAction::Clawback(exchange_id))
+    }
+
+    /// Starts the migration notice for `successor`.
+    pub fn announce_successor(successor: ActorId) -> Self {
+        Call::new(ExchangeAction::AnnounceSuccessor(successor))
+    }
+
+    pub fn export(cursor: Option<[u8; 32]>, limit: u32) -> Self {
+        Call::new(ExchangeAction::Export(cursor, limit))
+    }
+
+    /// Sends the locked value to the announced `successor`, which must have
+    /// confirmed the import. The message's gas also pays for the successor
+    /// scheduling the automatic refunds again.
+    pub fn retire(successor: ActorId) -> Self {
+        Call::new(ExchangeAction::Retire(successor))
+    }
+
+    pub fn set_limits(limits: ExchangeLimits) -> Self {
+        Call::new(ExchangeAction::SetLimits(limits))
+    }
+
+    pub fn set_compliance(compliance: ExchangeCompliance) -> Self {
+        Call::new(ExchangeAction::SetCompliance(compliance))
+    }
+
+    pub fn allow(accounts: Vec<ActorId>) -> Self {
+        Call::new(ExchangeAction::Allow(accounts))
+    }
+
+    pub fn disallow(accounts: Vec<ActorId>) -> Self {
+        Call::new(ExchangeAction::Disallow(accounts))
+    }
+}
+
+/// Builds a `Fund` or `FundWith` call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FundBuilder {
+    receiver: ActorId,
+    hashlock: [u8; 32],
+    timelock: u64,
+    value: u128,
+    options: FundOptions,
+}
+
+impl FundBuilder {
+    pub fn new(receiver: ActorId, hashlock: [u8; 32], timelock: u64) -> Self {
+        FundBuilder {
+            receiver,
+            hashlock,
+            timelock,
+            value: 0,
+            options: FundOptions::default(),
+        }
+    }
+
+    /// Value locked in the exchange.
+    pub fn value(mut self, value: u128) -> Self {
+        self.value = value;
+        self
+    }
+
+    /// Releases the value linearly over `duration` milliseconds after redeem.
+    pub fn vesting(mut self, duration: u64) -> Self {
+        self.options.vesting = Some(duration);
+        self
+    }
+
+    /// Reserves `gas` out of the message's gas limit for the automatic
+    /// refund at the timelock.
+    pub fn reservation(mut self, gas: u64) -> Self {
+        self.options.reservation = Some(gas);
+        self
+    }
+
+    /// Lets the sender refund before the timelock with the preimage of
+    /// `refund_hashlock`.
+    pub fn refund_hashlock(mut self, refund_hashlock: [u8; 32]) -> Self {
+        self.options.refund_hashlock = Some(refund_hashlock);
+        self
+    }
+
+    pub fn receiver(&self) -> ActorId {
+        self.receiver
+    }
+
+    pub fn hashlock(&self) -> [u8; 32] {
+        self.hashlock
+    }
+
+    pub fn timelock(&self) -> u64 {
+        self.timelock
+    }
+
+    pub fn build(&self) -> Call {
+        let action = if self.options == FundOptions::default() {
+            ExchangeAction::Fund(self.hashlock, self.timelock, self.receiver)
+        } else {
+            ExchangeAction::FundWith(self.hashlock, self.timelock, self.receiver, self.options)
+        };
+
+        Call {
+            action,
+            value: self.value,
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use exchange_io::ExchangeEvent;
+use gstd::codec;
+use std::fmt;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The transport could not deliver the message or read the reply.
+    Transport(String),
+    /// The program rejected the message, usually with its panic message.
+    Failed(String),
+    Decode(codec::Error),
+    /// The program replied with an event that does not match the call.
+    UnexpectedEvent(ExchangeEvent),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Transport(reason) => write!(f, "transport error: {reason}"),
+            Error::Failed(reason) => write!(f, "exchange failed: {reason}"),
+            Error::Decode(error) => write!(f, "could not decode reply: {error}"),
+            Error::UnexpectedEvent(event) => write!(f, "unexpected reply: {event:?}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,146 @@
+//! In-memory [`Transport`] over a `gtest` [`System`].
+
+use crate::{decode_event, decode_record, Call, Error, EventFeed, Result, Transport};
+use exchange_io::{ExchangeEvent, ExchangeInit};
+use gstd::ActorId;
+use gtest::{Program, RunResult, System};
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+/// Connection to a `gtest` exchange program, standing in for a node.
+///
+/// Every event the program broadcasts goes to a log shared by all handles
+/// of the connection, so a handle's [`EventFeed`] also sees what other
+/// parties caused through theirs. Blocks must be spent through
+/// [`GTestTransport::spend_blocks`] for the events of delayed messages to
+/// show up.
+pub struct GTestTransport<'a> {
+    system: &'a System,
+    program: Program<'a>,
+    log: Rc<RefCell<Vec<ExchangeEvent>>>,
+    /// Position in `log` of the next event to poll.
+    cursor: usize,
+}
+
+impl<'a> GTestTransport<'a> {
+    /// Wraps an already initialised exchange program.
+    pub fn new(system: &'a System, program: Program<'a>) -> Self {
+        GTestTransport {
+            system,
+            program,
+            log: Rc::default(),
+            cursor: 0,
+        }
+    }
+
+    /// Uploads the exchange wasm at `path` and initialises it from `owner`.
+    pub fn deploy(
+        system: &'a System,
+        path: impl AsRef<Path>,
+        owner: ActorId,
+        init: ExchangeInit,
+        value: u128,
+    ) -> Result<Self> {
+        let program = Program::from_file(system, path);
+
+        let result = program.send_with_value(bytes(owner), init, value);
+
+        if result.main_failed() {
+            return Err(Error::Failed(failure(&result, owner)));
+        }
+
+        Ok(GTestTransport::new(system, program))
+    }
+
+    /// Another handle on the same program, for another party. Its feed
+    /// starts after the events logged so far.
+    pub fn handle(&self) -> Self {
+        GTestTransport {
+            system: self.system,
+            program: self.system.get_program(self.program.id()),
+            log: Rc::clone(&self.log),
+            cursor: self.log.borrow().len(),
+        }
+    }
+
+    /// Spends `blocks` blocks of `system` and logs what the programs of
+    /// `transports` broadcast in them.
+    pub fn spend_blocks(system: &System, blocks: u32, transports: &[&GTestTransport<'_>]) {
+        for result in system.spend_blocks(blocks) {
+            for transport in transports {
+                transport.record(&result);
+            }
+        }
+    }
+
+    pub fn system(&self) -> &'a System {
+        self.system
+    }
+
+    pub fn program(&self) -> &Program<'a> {
+        &self.program
+    }
+
+    /// Logs the events this program broadcast while handling `result`.
+    fn record(&self, result: &RunResult) {
+        self.log.borrow_mut().extend(
+            result
+                .log()
+                .iter()
+                .filter(|log| log.source() == self.program.id())
+                .filter(|log| log.destination().as_ref() == ActorId::zero().as_ref())
+                .filter_map(|log| decode_record(log.payload()).ok())
+                .map(|record| record.event),
+        );
+    }
+}
+
+impl Transport for GTestTransport<'_> {
+    fn send(&mut self, from: ActorId, call: &Call) -> Result<ExchangeEvent> {
+        let result = self
+            .program
+            .send_with_value(bytes(from), call.action.clone(), call.value);
+
+        if result.main_failed() {
+            return Err(Error::Failed(failure(&result, from)));
+        }
+
+        self.record(&result);
+
+        result
+            .log()
+            .iter()
+            .filter(|log| log.destination().as_ref() == from.as_ref())
+            .find_map(|log| decode_event(log.payload()).ok())
+            .ok_or_else(|| Error::Transport("no reply from the exchange program".into()))
+    }
+
+    fn block_timestamp(&self) -> Result<u64> {
+        Ok(self.system.block_timestamp())
+    }
+}
+
+impl EventFeed for GTestTransport<'_> {
+    fn poll_events(&mut self) -> Result<Vec<ExchangeEvent>> {
+        let events = self.log.borrow()[self.cursor..].to_vec();
+
+        self.cursor += events.len();
+
+        Ok(events)
+    }
+}
+
+fn bytes(actor: ActorId) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(actor.as_ref());
+    bytes
+}
+
+/// Panic message the program replied to `to` with.
+fn failure(result: &RunResult, to: ActorId) -> String {
+    result
+        .log()
+        .iter()
+        .find(|log| log.destination().as_ref() == to.as_ref())
+        .map(|log| String::from_utf8_lossy(log.payload()).into_owned())
+        .unwrap_or_else(|| "message failed".into())
+}
@@ -0,0 +1,101 @@
+//! Host-side client for the HTLC exchange program.
+//!
+//! Builds [`ExchangeAction`] payloads, derives exchange ids and hashlocks,
+//! decodes [`ExchangeEvent`] replies and tracks swap status over any
+//! [`Transport`].
+
+mod call;
+mod error;
+#[cfg(feature = "gtest")]
+pub mod gtest;
+mod secret;
+mod tracker;
+mod transport;
+
+pub use call::{Call, FundBuilder};
+pub use error::{Error, Result};
+pub use exchange_io::{
+    exchange_id, redeem_payload, wrap_bytes, Exchange, ExchangeAction, ExchangeEvent,
+    ExchangeEventRecord, ExchangeState, FundOptions, RedeemSignature, SR25519_SIGNING_CONTEXT,
+};
+pub use secret::{hashlock, Secret};
+pub use tracker::{Swap, SwapTracker};
+pub use transport::{EventFeed, Transport};
+
+use gstd::{codec::Decode, ActorId};
+
+/// Decodes a raw reply payload of the exchange program.
+pub fn decode_event(mut payload: &[u8]) -> Result<ExchangeEvent> {
+    ExchangeEvent::decode(&mut payload).map_err(Error::Decode)
+}
+
+/// Decodes an event broadcast by the program to `ActorId::zero()`.
+pub fn decode_record(mut payload: &[u8]) -> Result<ExchangeEventRecord> {
+    ExchangeEventRecord::decode(&mut payload).map_err(Error::Decode)
+}
+
+/// Sends calls through a [`Transport`] and keeps a [`SwapTracker`] in sync
+/// with the replies.
+pub struct Client<T> {
+    transport: T,
+    tracker: SwapTracker,
+}
+
+impl<T: Transport> Client<T> {
+    pub fn new(transport: T) -> Self {
+        Client {
+            transport,
+            tracker: SwapTracker::default(),
+        }
+    }
+
+    /// Sends `call` as `from` and applies the reply to the tracker.
+    pub fn execute(&mut self, from: ActorId, call: &Call) -> Result<ExchangeEvent> {
+        let event = self.transport.send(from, call)?;
+
+        self.tracker.apply(from, call, &event);
+
+        Ok(event)
+    }
+
+    /// Funds a new exchange and returns its id.
+    pub fn fund(&mut self, from: ActorId, fund: FundBuilder) -> Result<[u8; 32]> {
+        match self.execute(from, &fund.build())? {
+            ExchangeEvent::Funded(exchange_id, ..) => Ok(exchange_id),
+            event => Err(Error::UnexpectedEvent(event)),
+        }
+    }
+
+    pub fn redeem(&mut self, from: ActorId, exchange_id: [u8; 32], secret: Secret) -> Result<()> {
+        match self.execute(from, &Call::redeem(exchange_id, secret))? {
+            ExchangeEvent::Redeemed(..) => Ok(()),
+            event => Err(Error::UnexpectedEvent(event)),
+        }
+    }
+
+    pub fn refund(&mut self, from: ActorId, exchange_id: [u8; 32]) -> Result<()> {
+        match self.execute(from, &Call::refund(exchange_id))? {
+            ExchangeEvent::Refunded(..) => Ok(()),
+            event => Err(Error::UnexpectedEvent(event)),
+        }
+    }
+
+    /// Tracked swap with its state brought up to date with the chain clock.
+    pub fn status(&self, exchange_id: &[u8; 32]) -> Result<Option<Swap>> {
+        let now = self.transport.block_timestamp()?;
+
+        Ok(self.tracker.status(exchange_id, now))
+    }
+
+    pub fn tracker(&self) -> &SwapTracker {
+        &self.tracker
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+}
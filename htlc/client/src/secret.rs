@@ -0,0 +1,32 @@
+use rand::{rngs::OsRng, RngCore};
+use sha2::Digest;
+
+/// Preimage of an exchange hashlock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Secret(pub [u8; 32]);
+
+impl Secret {
+    /// Draws a fresh secret from the operating system RNG.
+    pub fn random() -> Self {
+        let mut secret = [0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+        Secret(secret)
+    }
+
+    pub fn hashlock(&self) -> [u8; 32] {
+        hashlock(&self.0)
+    }
+}
+
+impl From<[u8; 32]> for Secret {
+    fn from(secret: [u8; 32]) -> Self {
+        Secret(secret)
+    }
+}
+
+/// SHA-256 of `secret`, as checked by the program on redeem.
+pub fn hashlock(secret: &[u8; 32]) -> [u8; 32] {
+    let mut output = [0u8; 32];
+    output.copy_from_slice(sha2::Sha256::digest(secret).as_slice());
+    output
+}
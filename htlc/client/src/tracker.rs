@@ -0,0 +1,132 @@
+use crate::{Call, Secret};
+use exchange_io::{Exchange, ExchangeAction, ExchangeEvent, ExchangeState, FundOptions, Vesting};
+use gstd::ActorId;
+use std::collections::BTreeMap;
+
+/// Client-side view of one exchange.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Swap {
+    pub exchange_id: [u8; 32],
+    pub exchange: Exchange,
+    /// Known once the receiver redeems.
+    pub secret: Option<Secret>,
+}
+
+/// Follows exchanges through the events the program replies with.
+#[derive(Debug, Clone, Default)]
+pub struct SwapTracker {
+    swaps: BTreeMap<[u8; 32], Swap>,
+}
+
+impl SwapTracker {
+    /// Records the reply to `call` sent by `from`.
+    pub fn apply(&mut self, from: ActorId, call: &Call, event: &ExchangeEvent) {
+        if let ExchangeEvent::Funded(exchange_id, receiver, value) = *event {
+            let (hashlock, timelock, options) = match call.action {
+                ExchangeAction::Fund(hashlock, timelock, _) => {
+                    (hashlock, timelock, FundOptions::default())
+                }
+                ExchangeAction::FundWith(hashlock, timelock, _, options) => {
+                    (hashlock, timelock, options)
+                }
+                _ => return,
+            };
+
+            let mut exchange = Exchange::new(hashlock, timelock, value, from, receiver);
+
+            exchange.vesting = options.vesting.map(|duration| Vesting {
+                duration,
+                ..Default::default()
+            });
+            exchange.refund_hashlock = options.refund_hashlock;
+            exchange.reservation = options.reservation;
+
+            self.track(exchange_id, exchange);
+        }
+
+        self.observe(event);
+    }
+
+    /// Records an event that may have been caused by someone else.
+    pub fn observe(&mut self, event: &ExchangeEvent) {
+        match event {
+            ExchangeEvent::Redeemed(exchange_id, secret) => {
+                if let Some(swap) = self.swaps.get_mut(exchange_id) {
+                    swap.exchange.state = ExchangeState::Redeemed;
+                    swap.secret = Some(Secret(*secret));
+                }
+            }
+            ExchangeEvent::Refunded(exchange_id) => {
+                if let Some(swap) = self.swaps.get_mut(exchange_id) {
+                    swap.exchange.state = ExchangeState::Refunded;
+                }
+            }
+            ExchangeEvent::Claimed(exchange_id, amount) => {
+                if let Some(vesting) = self
+                    .swaps
+                    .get_mut(exchange_id)
+                    .and_then(|swap| swap.exchange.vesting.as_mut())
+                {
+                    vesting.claimed += amount;
+                }
+            }
+            ExchangeEvent::Disputed(exchange_id) => {
+                if let Some(vesting) = self
+                    .swaps
+                    .get_mut(exchange_id)
+                    .and_then(|swap| swap.exchange.vesting.as_mut())
+                {
+                    vesting.disputed = true;
+                }
+            }
+            ExchangeEvent::ClawedBack(exchange_id, amount) => {
+                if let Some(swap) = self.swaps.get_mut(exchange_id) {
+                    swap.exchange.value -= amount;
+                }
+            }
+            ExchangeEvent::Exported(_, exchanges, _) => {
+                for (exchange_id, exchange) in exchanges {
+                    self.track(*exchange_id, exchange.clone());
+                }
+            }
+            ExchangeEvent::Retired(..) => {
+                for swap in self.swaps.values_mut() {
+                    if swap.exchange.outstanding() > 0 {
+                        swap.exchange.state = ExchangeState::Migrated;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Starts tracking an exchange read from the program.
+    pub fn track(&mut self, exchange_id: [u8; 32], exchange: Exchange) {
+        let secret = self.swaps.get(&exchange_id).and_then(|swap| swap.secret);
+
+        self.swaps.insert(
+            exchange_id,
+            Swap {
+                exchange_id,
+                exchange,
+                secret,
+            },
+        );
+    }
+
+    /// Tracked swap, reported as `Expired` once a funded exchange passes its
+    /// timelock at `now`.
+    pub fn status(&self, exchange_id: &[u8; 32], now: u64) -> Option<Swap> {
+        let mut swap = self.swaps.get(exchange_id)?.clone();
+
+        if swap.exchange.state == ExchangeState::Funded && now >= swap.exchange.timelock {
+            swap.exchange.state = ExchangeState::Expired;
+        }
+
+        Some(swap)
+    }
+
+    pub fn swaps(&self) -> impl Iterator<Item = &Swap> {
+        self.swaps.values()
+    }
+}
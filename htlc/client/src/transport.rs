@@ -0,0 +1,37 @@
+use crate::{Call, Result};
+use exchange_io::ExchangeEvent;
+use gstd::ActorId;
+
+/// Delivers calls to a deployed exchange program.
+///
+/// Implemented for `gtest` behind the `gtest` feature; node-backed
+/// implementations live with the services that own the keys.
+pub trait Transport {
+    /// Sends `call` on behalf of `from` and waits for the program's reply.
+    fn send(&mut self, from: ActorId, call: &Call) -> Result<ExchangeEvent>;
+
+    /// Current block timestamp of the program's chain, in milliseconds.
+    fn block_timestamp(&self) -> Result<u64>;
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    fn send(&mut self, from: ActorId, call: &Call) -> Result<ExchangeEvent> {
+        (**self).send(from, call)
+    }
+
+    fn block_timestamp(&self) -> Result<u64> {
+        (**self).block_timestamp()
+    }
+}
+
+/// Events emitted by the exchange program, whoever caused them.
+pub trait EventFeed {
+    /// Events observed since the previous poll, oldest first.
+    fn poll_events(&mut self) -> Result<Vec<ExchangeEvent>>;
+}
+
+impl<T: EventFeed + ?Sized> EventFeed for &mut T {
+    fn poll_events(&mut self) -> Result<Vec<ExchangeEvent>> {
+        (**self).poll_events()
+    }
+}
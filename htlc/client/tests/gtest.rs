@@ -0,0 +1,112 @@
+#![cfg(feature = "gtest")]
+
+use exchange_client::{
+    gtest::GTestTransport, Call, Client, Error, EventFeed, ExchangeEvent, ExchangeState, Secret,
+};
+use exchange_io::ExchangeInit;
+use gstd::ActorId;
+use gtest::System;
+
+/// Built by `cargo build --release` in the program crate.
+const WASM: &str = "../target/wasm32-unknown-unknown/release/htlc.opt.wasm";
+
+const OWNER: u64 = 1;
+const SENDER: u64 = 10;
+const RECEIVER: u64 = 11;
+const VALUE: u128 = 10_000_000_000_000;
+
+fn deploy(system: &System) -> Client<GTestTransport<'_>> {
+    system.init_logger();
+    system.mint_to(SENDER, 10 * VALUE);
+
+    let transport = GTestTransport::deploy(system, WASM, OWNER.into(), ExchangeInit::default(), 0)
+        .expect("Failed to deploy the exchange");
+
+    Client::new(transport)
+}
+
+#[test]
+fn fund_then_redeem() {
+    let system = System::new();
+    let mut client = deploy(&system);
+
+    let (sender, receiver) = (ActorId::from(SENDER), ActorId::from(RECEIVER));
+    let secret = Secret::random();
+    let timelock = system.block_timestamp() + 60_000;
+
+    let exchange_id = client
+        .fund(
+            sender,
+            Call::fund(receiver, secret.hashlock(), timelock).value(VALUE),
+        )
+        .unwrap();
+
+    assert_eq!(
+        exchange_id,
+        exchange_io::exchange_id(sender, receiver, secret.hashlock())
+    );
+
+    let swap = client.status(&exchange_id).unwrap().unwrap();
+    assert_eq!(swap.exchange.state, ExchangeState::Funded);
+    assert_eq!(swap.exchange.value, VALUE);
+
+    // Only the receiver may redeem.
+    assert!(matches!(
+        client.redeem(sender, exchange_id, secret),
+        Err(Error::Failed(_))
+    ));
+
+    client.redeem(receiver, exchange_id, secret).unwrap();
+
+    let swap = client.status(&exchange_id).unwrap().unwrap();
+    assert_eq!(swap.exchange.state, ExchangeState::Redeemed);
+    assert_eq!(swap.secret, Some(secret));
+
+    assert_eq!(
+        client.transport_mut().poll_events().unwrap(),
+        [
+            ExchangeEvent::Funded(exchange_id, receiver, VALUE),
+            ExchangeEvent::Redeemed(exchange_id, secret.0),
+        ]
+    );
+}
+
+#[test]
+fn fund_then_refund() {
+    let system = System::new();
+    let mut client = deploy(&system);
+
+    let (sender, receiver) = (ActorId::from(SENDER), ActorId::from(RECEIVER));
+    let secret = Secret::random();
+    let timelock = system.block_timestamp() + 60_000;
+
+    let exchange_id = client
+        .fund(
+            sender,
+            Call::fund(receiver, secret.hashlock(), timelock).value(VALUE),
+        )
+        .unwrap();
+
+    assert!(matches!(
+        client.refund(sender, exchange_id),
+        Err(Error::Failed(_))
+    ));
+
+    while system.block_timestamp() < timelock {
+        system.spend_blocks(1);
+    }
+
+    let swap = client.status(&exchange_id).unwrap().unwrap();
+    assert_eq!(swap.exchange.state, ExchangeState::Expired);
+
+    client.refund(sender, exchange_id).unwrap();
+
+    let swap = client.status(&exchange_id).unwrap().unwrap();
+    assert_eq!(swap.exchange.state, ExchangeState::Refunded);
+
+    // The secret was never revealed, so the receiver cannot redeem.
+    assert!(matches!(
+        client.redeem(receiver, exchange_id, secret),
+        Err(Error::Failed(_))
+    ));
+}
@@ -0,0 +1,807 @@
+#![no_std]
+
+//! Exchange logic shared by the program and the simulator. Everything the
+//! runtime provides (caller, clock, transfers, gas reservations) goes
+//! through [`Env`], so the same code runs on-chain and natively.
+
+extern crate alloc;
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+use core::ops::Bound;
+use exchange_io::*;
+use sha2::Digest;
+
+/// Block time the runtimes assume when they turn a timelock into a delay.
+/// On chains with other block times, wake-ups run early or late.
+pub const EXPECTED_BLOCK_TIME: u64 = 3_000;
+
+/// Gas an early wake-up keeps to finish, handing the rest of its gas to the
+/// next one.
+pub const RESCHEDULE_GAS: u64 = 1_000_000_000;
+
+/// Exchanges of a listing and the cursor of the next page.
+type Page = (Vec<([u8; 32], Exchange)>, Option<[u8; 32]>);
+
+/// What the exchange needs from the chain it runs on.
+pub trait Env {
+    /// Account that sent the message being handled.
+    fn source(&self) -> ActorId;
+    /// Value attached to that message.
+    fn value(&self) -> u128;
+    /// Block timestamp, in milliseconds.
+    fn now(&self) -> u64;
+    /// Account of the program itself.
+    fn program_id(&self) -> ActorId;
+    /// Sends `value` out of the program.
+    fn send(&mut self, to: ActorId, value: u128);
+    /// Sends `action` with `value` to another exchange program.
+    fn send_action(&mut self, to: ActorId, action: ExchangeAction, value: u128);
+    /// Reserves `gas` and sends `Expire(exchange_id)` from the reservation,
+    /// delayed until `timelock`, or right away if it has passed. The wake-up
+    /// carries the reserved gas and pays for the refund with it.
+    fn schedule_expiry(&mut self, exchange_id: [u8; 32], timelock: u64, gas: u64);
+    /// Sends the `Expire(exchange_id)` being handled again, delayed until
+    /// `timelock`, with the gas it has left.
+    fn reschedule_expiry(&mut self, exchange_id: [u8; 32], timelock: u64);
+}
+
+/// Blocks to delay an `Expire` sent at `now` by, so it runs at `timelock`
+/// if blocks come every [`EXPECTED_BLOCK_TIME`]. Zero once it has passed.
+pub fn expiry_delay(now: u64, timelock: u64) -> u64 {
+    timelock.saturating_sub(now).div_ceil(EXPECTED_BLOCK_TIME)
+}
+
+/// Gas an early wake-up left with `gas_available` hands to the next one.
+pub fn reschedule_gas(gas_available: u64) -> u64 {
+    gas_available
+        .checked_sub(RESCHEDULE_GAS)
+        .expect("Not enough gas left to reschedule expiry")
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Exchanges {
+    owner: ActorId,
+    frozen: bool,
+    exchanges: BTreeMap<[u8; 32], Exchange>,
+    limits: ExchangeLimits,
+    compliance: ExchangeCompliance,
+    allowlist: BTreeSet<ActorId>,
+    exposures: BTreeMap<ActorId, Exposure>,
+    /// Fund time of exchanges funded on this program, until they settle.
+    funded_at: BTreeMap<[u8; 32], u64>,
+    stats: ExchangeStats,
+    /// Import still waiting for the predecessor's locked value.
+    pending_import: Option<PendingImport>,
+    migration_notice: u64,
+    /// Program the owner announced to migrate to.
+    successor: Option<Successor>,
+}
+
+/// What a sender currently has locked in funded exchanges.
+#[derive(Debug, Clone, Copy, Default)]
+struct Exposure {
+    exchanges: u32,
+    locked: u128,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Successor {
+    program: ActorId,
+    /// Block timestamp the migration notice ends at.
+    exportable_at: u64,
+    /// Whether `program` confirmed importing the export.
+    confirmed: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingImport {
+    predecessor: ActorId,
+    checksum: [u8; 32],
+    locked: u128,
+}
+
+impl Exchanges {
+    /// Sets up the exchange for the `init` message, owned by its sender.
+    /// An import keeps it frozen until the predecessor settles it.
+    pub fn new(env: &mut impl Env, init: ExchangeInit) -> Self {
+        let mut store = Exchanges {
+            owner: env.source(),
+            limits: init.limits,
+            compliance: init.compliance,
+            migration_notice: init.migration_notice,
+            ..Default::default()
+        };
+
+        if let Some(import) = init.import {
+            assert!(
+                import.version == EXPORT_VERSION,
+                "Unsupported export version"
+            );
+            assert!(
+                exchanges_checksum(&import.exchanges) == import.checksum,
+                "Invalid import checksum"
+            );
+
+            let mut locked = 0u128;
+
+            for (exchange_id, exchange) in import.exchanges {
+                let outstanding = exchange.outstanding();
+
+                assert!(outstanding > 0, "Only live exchanges can be imported");
+
+                locked = locked
+                    .checked_add(outstanding)
+                    .expect("Imported value overflow");
+
+                if exchange.state == ExchangeState::Funded {
+                    let entry = store.exposures.entry(exchange.sender).or_default();
+                    entry.exchanges += 1;
+                    entry.locked += exchange.value;
+                }
+
+                assert!(
+                    store.exchanges.insert(exchange_id, exchange).is_none(),
+                    "Duplicate exchange"
+                );
+            }
+
+            store.frozen = true;
+            store.pending_import = Some(PendingImport {
+                predecessor: import.predecessor,
+                checksum: import.checksum,
+                locked,
+            });
+
+            env.send_action(
+                import.predecessor,
+                ExchangeAction::ConfirmImport(import.checksum),
+                0,
+            );
+        }
+
+        store
+    }
+
+    /// Handles every action but `Expire`, which only the runtime sends and
+    /// goes to [`Exchanges::expire`].
+    pub fn handle(&mut self, env: &mut impl Env, action: ExchangeAction) -> ExchangeEvent {
+        match action {
+            ExchangeAction::Export(..) | ExchangeAction::SettleImport(..) => {}
+            ExchangeAction::Retire(..) | ExchangeAction::ConfirmImport(..) => {
+                assert!(self.frozen, "Exchange must be exported first")
+            }
+            _ => assert!(!self.frozen, "Exchange is frozen for migration"),
+        }
+
+        match action {
+            ExchangeAction::Fund(hashlock, timelock, receiver) => {
+                self.fund(env, hashlock, timelock, receiver, FundOptions::default())
+            }
+            ExchangeAction::FundWith(hashlock, timelock, receiver, options) => {
+                self.fund(env, hashlock, timelock, receiver, options)
+            }
+            ExchangeAction::Redeem(exchange_id, secret) => {
+                let receiver = self.exchange(&exchange_id).receiver;
+
+                assert!(receiver == env.source(), "Only receiver can redeem");
+
+                self.redeem(env, exchange_id, secret, receiver)
+            }
+            ExchangeAction::RedeemWithSignature(exchange_id, secret, payout, signature) => {
+                let exchange = self.exchange(&exchange_id);
+
+                assert!(!payout.is_zero(), "Invalid payout address");
+                assert!(
+                    exchange.vesting.is_none(),
+                    "Vesting exchanges must be redeemed by the receiver"
+                );
+                assert!(
+                    verify_signature(
+                        exchange.receiver,
+                        &redeem_payload(env.program_id(), exchange_id, secret, payout),
+                        &signature
+                    ),
+                    "Invalid signature"
+                );
+
+                self.redeem(env, exchange_id, secret, payout)
+            }
+            ExchangeAction::Refund(exchange_id) => {
+                assert!(
+                    env.now() >= self.exchange(&exchange_id).timelock,
+                    "Exchange not expired"
+                );
+
+                self.refund(env, exchange_id)
+            }
+            ExchangeAction::RefundWithSecret(exchange_id, refund_secret) => {
+                let refund_hashlock = self
+                    .exchange(&exchange_id)
+                    .refund_hashlock
+                    .expect("Exchange has no refund hashlock");
+
+                assert!(
+                    refund_hashlock == sha2_256(&refund_secret),
+                    "Invalid refund secret"
+                );
+
+                self.refund(env, exchange_id)
+            }
+            ExchangeAction::Claim(exchange_id) => {
+                let exchange = self.exchange_mut(&exchange_id);
+
+                assert!(
+                    exchange.state == ExchangeState::Redeemed,
+                    "Invalid exchange state"
+                );
+                assert!(exchange.receiver == env.source(), "Only receiver can claim");
+
+                let vesting = exchange.vesting.as_mut().expect("Exchange is not vesting");
+
+                let amount = vesting.vested(exchange.value, env.now()) - vesting.claimed;
+
+                assert!(amount > 0, "Nothing to claim");
+
+                vesting.claimed += amount;
+
+                env.send(exchange.receiver, amount);
+
+                self.stats.redeemed.add_value(amount);
+
+                ExchangeEvent::Claimed(exchange_id, amount)
+            }
+            ExchangeAction::Dispute(exchange_id) => {
+                assert!(self.owner == env.source(), "Only owner can raise disputes");
+
+                let exchange = self.exchange_mut(&exchange_id);
+
+                assert!(
+                    matches!(
+                        exchange.state,
+                        ExchangeState::Funded | ExchangeState::Redeemed
+                    ),
+                    "Invalid exchange state"
+                );
+
+                let vesting = exchange.vesting.as_mut().expect("Exchange is not vesting");
+
+                vesting.disputed = true;
+
+                ExchangeEvent::Disputed(exchange_id)
+            }
+            ExchangeAction::Clawback(exchange_id) => {
+                let exchange = self.exchange_mut(&exchange_id);
+
+                assert!(
+                    exchange.state == ExchangeState::Redeemed,
+                    "Invalid exchange state"
+                );
+                assert!(exchange.sender == env.source(), "Only sender can claw back");
+
+                let vesting = exchange.vesting.as_mut().expect("Exchange is not vesting");
+
+                assert!(vesting.disputed, "Exchange is not disputed");
+
+                let now = env.now();
+                let vested = vesting.vested(exchange.value, now);
+                let amount = exchange.value - vested;
+
+                assert!(amount > 0, "Nothing to claw back");
+
+                // Cut the schedule at `now`: what vested so far stays claimable.
+                exchange.value = vested;
+                vesting.duration = now.saturating_sub(vesting.start);
+
+                env.send(exchange.sender, amount);
+
+                ExchangeEvent::ClawedBack(exchange_id, amount)
+            }
+            ExchangeAction::Export(cursor, limit) => {
+                assert!(self.owner == env.source(), "Only owner can export");
+                assert!(limit > 0, "Page limit must be greater than 0");
+
+                let successor = self.successor.expect("No successor announced");
+
+                assert!(
+                    env.now() >= successor.exportable_at,
+                    "Migration notice not over"
+                );
+
+                self.frozen = true;
+
+                let (page, next) = self.page(cursor, limit, |exchange| exchange.outstanding() > 0);
+
+                ExchangeEvent::Exported(EXPORT_VERSION, page, next)
+            }
+            ExchangeAction::ConfirmImport(checksum) => {
+                let successor = self.successor.expect("No successor announced");
+
+                assert!(
+                    successor.program == env.source(),
+                    "Only the announced successor can confirm the import"
+                );
+                assert!(
+                    checksum == exchanges_checksum(&self.live_exchanges()),
+                    "Invalid import checksum"
+                );
+
+                self.successor = Some(Successor {
+                    confirmed: true,
+                    ..successor
+                });
+
+                ExchangeEvent::ImportConfirmed(env.source())
+            }
+            ExchangeAction::Retire(successor) => {
+                assert!(self.owner == env.source(), "Only owner can retire");
+
+                let announced = self.successor.expect("No successor announced");
+
+                assert!(
+                    announced.program == successor,
+                    "Successor was not announced"
+                );
+                assert!(
+                    announced.confirmed,
+                    "Successor has not confirmed the import"
+                );
+                assert!(self.pending_import.is_none(), "Import not settled");
+
+                let checksum = exchanges_checksum(&self.live_exchanges());
+                let mut locked = 0u128;
+
+                for exchange in self.exchanges.values_mut() {
+                    let outstanding = exchange.outstanding();
+
+                    if outstanding > 0 {
+                        locked += outstanding;
+                        exchange.state = ExchangeState::Migrated;
+                    }
+                }
+
+                self.exposures.clear();
+                self.funded_at.clear();
+
+                env.send_action(successor, ExchangeAction::SettleImport(checksum), locked);
+
+                ExchangeEvent::Retired(successor, locked)
+            }
+            ExchangeAction::SettleImport(checksum) => {
+                let import = self.pending_import.take().expect("No import to settle");
+
+                assert!(
+                    import.predecessor == env.source(),
+                    "Only the predecessor can settle the import"
+                );
+                assert!(import.checksum == checksum, "Invalid import checksum");
+                assert!(
+                    env.value() == import.locked,
+                    "Attached value must match imported locked value"
+                );
+
+                self.frozen = false;
+
+                // Wake-ups scheduled by the predecessor found it frozen.
+                for (exchange_id, exchange) in &self.exchanges {
+                    if let (ExchangeState::Funded, Some(gas)) =
+                        (exchange.state, exchange.reservation)
+                    {
+                        env.schedule_expiry(*exchange_id, exchange.timelock, gas);
+                    }
+                }
+
+                ExchangeEvent::ImportSettled(import.locked)
+            }
+            ExchangeAction::AnnounceSuccessor(program) => {
+                assert!(
+                    self.owner == env.source(),
+                    "Only owner can announce a successor"
+                );
+                assert!(
+                    !program.is_zero() && program != self.owner,
+                    "Invalid successor address"
+                );
+
+                let exportable_at = env.now().saturating_add(self.migration_notice);
+
+                self.successor = Some(Successor {
+                    program,
+                    exportable_at,
+                    confirmed: false,
+                });
+
+                ExchangeEvent::SuccessorAnnounced(program, exportable_at)
+            }
+            ExchangeAction::Expire(_) => panic!("Only the program can expire exchanges"),
+            ExchangeAction::SetLimits(limits) => {
+                assert!(self.owner == env.source(), "Only owner can set limits");
+
+                self.limits = limits;
+
+                ExchangeEvent::LimitsSet(limits)
+            }
+            ExchangeAction::SetCompliance(compliance) => {
+                assert!(self.owner == env.source(), "Only owner can set compliance");
+
+                self.compliance = compliance;
+
+                ExchangeEvent::ComplianceSet(compliance)
+            }
+            ExchangeAction::Allow(accounts) => {
+                assert!(
+                    self.owner == env.source(),
+                    "Only owner can change allowlist"
+                );
+
+                self.allowlist.extend(accounts.iter().copied());
+
+                ExchangeEvent::Allowed(accounts)
+            }
+            ExchangeAction::Disallow(accounts) => {
+                assert!(
+                    self.owner == env.source(),
+                    "Only owner can change allowlist"
+                );
+
+                for account in &accounts {
+                    self.allowlist.remove(account);
+                }
+
+                ExchangeEvent::Disallowed(accounts)
+            }
+        }
+    }
+
+    /// Refunds an exchange that reached its timelock still funded. A wake-up
+    /// that arrives early, because blocks came faster than expected, waits
+    /// out the rest of the timelock.
+    pub fn expire(&mut self, env: &mut impl Env, exchange_id: [u8; 32]) -> Option<ExchangeEvent> {
+        if self.frozen {
+            return None;
+        }
+
+        let exchange = self.exchanges.get_mut(&exchange_id)?;
+
+        if exchange.state != ExchangeState::Funded {
+            return None;
+        }
+
+        if env.now() < exchange.timelock {
+            env.reschedule_expiry(exchange_id, exchange.timelock);
+            return None;
+        }
+
+        env.send(exchange.sender, exchange.value);
+
+        exchange.state = ExchangeState::Refunded;
+
+        let (sender, receiver, value) = (exchange.sender, exchange.receiver, exchange.value);
+        self.release(sender, value);
+        self.record_refund(exchange_id, receiver, value);
+
+        Some(ExchangeEvent::Refunded(exchange_id))
+    }
+
+    /// Panics unless the allowlist lets `sender` fund an exchange for
+    /// `receiver`. Returns the registry to ask under `Registry` compliance.
+    pub fn check_compliance(&self, sender: ActorId, receiver: ActorId) -> Option<ActorId> {
+        match self.compliance {
+            ExchangeCompliance::Open => None,
+            ExchangeCompliance::Allowlist => {
+                assert!(
+                    self.allowlist.contains(&sender) && self.allowlist.contains(&receiver),
+                    "Counterparty not allowed"
+                );
+
+                None
+            }
+            ExchangeCompliance::Registry(registry) => Some(registry),
+        }
+    }
+
+    pub fn get(&self, exchange_id: &[u8; 32]) -> Option<&Exchange> {
+        self.exchanges.get(exchange_id)
+    }
+
+    pub fn exchanges(&self) -> impl Iterator<Item = (&[u8; 32], &Exchange)> {
+        self.exchanges.iter()
+    }
+
+    pub fn stats(&self) -> &ExchangeStats {
+        &self.stats
+    }
+
+    pub fn owner(&self) -> ActorId {
+        self.owner
+    }
+
+    pub fn frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn limits(&self) -> ExchangeLimits {
+        self.limits
+    }
+
+    pub fn compliance(&self) -> ExchangeCompliance {
+        self.compliance
+    }
+
+    pub fn is_allowed(&self, account: &ActorId) -> bool {
+        self.allowlist.contains(account)
+    }
+
+    /// Answers a `state` query.
+    pub fn query(&self, query: ExchangeQuery) -> ExchangeReply {
+        match query {
+            ExchangeQuery::All => ExchangeReply::Storage(self.storage()),
+            ExchangeQuery::Exchange(exchange_id) => {
+                ExchangeReply::Exchange(self.exchanges.get(&exchange_id).cloned())
+            }
+            ExchangeQuery::ByParty(party, cursor, limit) => {
+                let (page, next) = self.page(cursor, limit.min(MAX_PAGE), |exchange| {
+                    exchange.sender == party || exchange.receiver == party
+                });
+
+                ExchangeReply::Exchanges(page, next)
+            }
+            ExchangeQuery::ByState(state, cursor, limit) => {
+                let (page, next) = self.page(cursor, limit.min(MAX_PAGE), |exchange| {
+                    exchange.state == state
+                });
+
+                ExchangeReply::Exchanges(page, next)
+            }
+        }
+    }
+
+    /// Full state, as `state` returns it for [`ExchangeQuery::All`].
+    pub fn storage(&self) -> ExchangeStorage {
+        ExchangeStorage {
+            exchanges: self
+                .exchanges
+                .iter()
+                .map(|(exchange_id, exchange)| (*exchange_id, exchange.clone()))
+                .collect(),
+            stats: self.stats.clone(),
+        }
+    }
+
+    /// Up to `limit` exchanges matching `filter` after `cursor`, and the
+    /// cursor of the next page if more remain.
+    fn page(
+        &self,
+        cursor: Option<[u8; 32]>,
+        limit: u32,
+        filter: impl Fn(&Exchange) -> bool,
+    ) -> Page {
+        let start = cursor.map_or(Bound::Unbounded, Bound::Excluded);
+
+        let mut page: Vec<([u8; 32], Exchange)> = self
+            .exchanges
+            .range((start, Bound::Unbounded))
+            .filter(|(_, exchange)| filter(exchange))
+            .take(limit as usize + 1)
+            .map(|(exchange_id, exchange)| (*exchange_id, exchange.clone()))
+            .collect();
+
+        let next = if page.len() > limit as usize {
+            page.pop();
+            page.last().map(|(exchange_id, _)| *exchange_id)
+        } else {
+            None
+        };
+
+        (page, next)
+    }
+
+    /// Exchanges with value still locked, in export order.
+    fn live_exchanges(&self) -> Vec<([u8; 32], Exchange)> {
+        self.exchanges
+            .iter()
+            .filter(|(_, exchange)| exchange.outstanding() > 0)
+            .map(|(exchange_id, exchange)| (*exchange_id, exchange.clone()))
+            .collect()
+    }
+
+    fn exchange(&self, exchange_id: &[u8; 32]) -> &Exchange {
+        self.exchanges.get(exchange_id).expect("Exchange not found")
+    }
+
+    fn exchange_mut(&mut self, exchange_id: &[u8; 32]) -> &mut Exchange {
+        self.exchanges
+            .get_mut(exchange_id)
+            .expect("Exchange not found")
+    }
+
+    fn fund(
+        &mut self,
+        env: &mut impl Env,
+        hashlock: [u8; 32],
+        timelock: u64,
+        receiver: ActorId,
+        options: FundOptions,
+    ) -> ExchangeEvent {
+        assert!(env.value() > 0, "Amount must be greater than 0");
+        assert!(timelock > env.now(), "Unlock time must be in the future");
+        assert!(!receiver.is_zero(), "Invalid receiver address");
+
+        let exchange_id = exchange_id(env.source(), receiver, hashlock);
+
+        assert!(
+            !self.exchanges.contains_key(&exchange_id),
+            "Duplicate exchange",
+        );
+
+        let sender = env.source();
+        let value = env.value();
+
+        self.lock(sender, value);
+
+        let mut exchange = Exchange::new(hashlock, timelock, value, sender, receiver);
+
+        exchange.vesting = options.vesting.map(|duration| Vesting {
+            duration,
+            ..Default::default()
+        });
+        exchange.refund_hashlock = options.refund_hashlock;
+        exchange.reservation = options.reservation;
+
+        self.exchanges.insert(exchange_id, exchange);
+        self.funded_at.insert(exchange_id, env.now());
+        self.stats.funded.add(value);
+
+        if let Some(gas) = options.reservation {
+            env.schedule_expiry(exchange_id, timelock, gas);
+        }
+
+        ExchangeEvent::Funded(exchange_id, receiver, value)
+    }
+
+    /// Reveals the secret and pays the receiver's share to `payout`.
+    fn redeem(
+        &mut self,
+        env: &mut impl Env,
+        exchange_id: [u8; 32],
+        secret: [u8; 32],
+        payout: ActorId,
+    ) -> ExchangeEvent {
+        let exchange = self.exchange_mut(&exchange_id);
+
+        assert!(
+            exchange.state == ExchangeState::Funded,
+            "Invalid exchange state"
+        );
+        assert!(exchange.hashlock == sha2_256(&secret), "Invalid secret");
+
+        let amount = match exchange.vesting.as_mut() {
+            Some(vesting) => {
+                vesting.start = env.now();
+                vesting.claimed = vesting.vested(exchange.value, vesting.start);
+                vesting.claimed
+            }
+            None => exchange.value,
+        };
+
+        if amount > 0 {
+            env.send(payout, amount);
+        }
+
+        exchange.state = ExchangeState::Redeemed;
+
+        let (sender, receiver, value) = (exchange.sender, exchange.receiver, exchange.value);
+        self.release(sender, value);
+
+        self.stats.redeemed.add(amount);
+        self.stats.receivers.entry(receiver).or_default().redeemed += 1;
+
+        if let Some(funded_at) = self.funded_at.remove(&exchange_id) {
+            self.stats.redeem_time += (env.now() - funded_at) as u128;
+            self.stats.timed_redeems += 1;
+        }
+
+        ExchangeEvent::Redeemed(exchange_id, secret)
+    }
+
+    /// Returns a funded exchange to its sender. Callers check the timelock
+    /// or the refund secret.
+    fn refund(&mut self, env: &mut impl Env, exchange_id: [u8; 32]) -> ExchangeEvent {
+        let exchange = self.exchange_mut(&exchange_id);
+
+        assert!(
+            exchange.state == ExchangeState::Funded,
+            "Invalid exchange state"
+        );
+        assert!(exchange.sender == env.source(), "Only sender can refund");
+
+        env.send(exchange.sender, exchange.value);
+
+        exchange.state = ExchangeState::Refunded;
+
+        let (sender, receiver, value) = (exchange.sender, exchange.receiver, exchange.value);
+        self.release(sender, value);
+        self.record_refund(exchange_id, receiver, value);
+
+        ExchangeEvent::Refunded(exchange_id)
+    }
+
+    fn record_refund(&mut self, exchange_id: [u8; 32], receiver: ActorId, value: u128) {
+        self.funded_at.remove(&exchange_id);
+        self.stats.refunded.add(value);
+        self.stats.receivers.entry(receiver).or_default().refunded += 1;
+    }
+
+    fn lock(&mut self, sender: ActorId, value: u128) {
+        let exposure = self.exposures.entry(sender).or_default();
+
+        if let Some(max_exchanges) = self.limits.max_exchanges {
+            assert!(
+                exposure.exchanges < max_exchanges,
+                "Too many open exchanges"
+            );
+        }
+
+        let locked = exposure
+            .locked
+            .checked_add(value)
+            .expect("Locked value overflow");
+
+        if let Some(max_locked_value) = self.limits.max_locked_value {
+            assert!(locked <= max_locked_value, "Locked value limit exceeded");
+        }
+
+        exposure.exchanges += 1;
+        exposure.locked = locked;
+    }
+
+    fn release(&mut self, sender: ActorId, value: u128) {
+        if let Some(exposure) = self.exposures.get_mut(&sender) {
+            exposure.exchanges = exposure.exchanges.saturating_sub(1);
+            exposure.locked = exposure.locked.saturating_sub(value);
+
+            if exposure.exchanges == 0 {
+                self.exposures.remove(&sender);
+            }
+        }
+    }
+}
+
+/// Checks a signature made by the key behind `signer` over `payload`, as
+/// wrapped by wallets.
+fn verify_signature(signer: ActorId, payload: &[u8], signature: &RedeemSignature) -> bool {
+    let public: [u8; 32] = signer.into();
+    let message = wrap_bytes(payload);
+
+    match signature {
+        RedeemSignature::Sr25519(signature) => {
+            let (Ok(public), Ok(signature)) = (
+                schnorrkel::PublicKey::from_bytes(&public),
+                schnorrkel::Signature::from_bytes(signature),
+            ) else {
+                return false;
+            };
+
+            public
+                .verify_simple(SR25519_SIGNING_CONTEXT, &message, &signature)
+                .is_ok()
+        }
+        RedeemSignature::Ed25519(signature) => {
+            let Ok(public) = ed25519_dalek::VerifyingKey::from_bytes(&public) else {
+                return false;
+            };
+
+            public
+                .verify_strict(&message, &ed25519_dalek::Signature::from_bytes(signature))
+                .is_ok()
+        }
+    }
+}
+
+fn sha2_256(data: &[u8]) -> [u8; 32] {
+    let mut output = [0u8; 32];
+    output.copy_from_slice(sha2::Sha256::digest(data).as_slice());
+    output
+}
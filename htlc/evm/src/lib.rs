@@ -0,0 +1,653 @@
+//! Codec between the Vara exchange and the standard EVM HTLC ABI:
+//!
+//! ```text
+//! function newContract(address receiver, bytes32 hashlock, uint256 timelock) payable
+//! function withdraw(bytes32 contractId, bytes32 preimage)
+//! function refund(bytes32 contractId)
+//!
+//! event LogHTLCNew(bytes32 indexed contractId, address indexed sender,
+//!     address indexed receiver, uint256 amount, bytes32 hashlock, uint256 timelock)
+//! event LogHTLCWithdraw(bytes32 indexed contractId)
+//! event LogHTLCRefund(bytes32 indexed contractId)
+//! ```
+//!
+//! EVM timelocks are Unix seconds, Vara ones milliseconds. Addresses map to
+//! actor ids left-padded with zeros, the way the ABI encodes them.
+
+use exchange_io::{Exchange, ExchangeAction, ExchangeEvent};
+use gstd::ActorId;
+use std::fmt;
+use tiny_keccak::{Hasher, Keccak};
+
+pub type Address = [u8; 20];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    InvalidLength,
+    UnknownSelector([u8; 4]),
+    UnknownEvent([u8; 32]),
+    /// A word does not fit the Rust type it is decoded into.
+    Overflow,
+    /// The actor id has no EVM address, or a word is not a valid address.
+    InvalidAddress,
+    /// The action or event has no EVM counterpart.
+    Unsupported,
+    /// `LogHTLCWithdraw` does not carry the preimage; it must come from the
+    /// `withdraw` call data.
+    MissingPreimage,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidLength => write!(f, "invalid ABI data length"),
+            Error::UnknownSelector(selector) => write!(f, "unknown selector {selector:02x?}"),
+            Error::UnknownEvent(topic) => write!(f, "unknown event {topic:02x?}"),
+            Error::Overflow => write!(f, "value out of range"),
+            Error::InvalidAddress => write!(f, "invalid address"),
+            Error::Unsupported => write!(f, "no EVM counterpart"),
+            Error::MissingPreimage => write!(f, "withdraw preimage missing"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+pub const NEW_CONTRACT: &str = "newContract(address,bytes32,uint256)";
+pub const WITHDRAW: &str = "withdraw(bytes32,bytes32)";
+pub const REFUND: &str = "refund(bytes32)";
+
+pub const LOG_HTLC_NEW: &str = "LogHTLCNew(bytes32,address,address,uint256,bytes32,uint256)";
+pub const LOG_HTLC_WITHDRAW: &str = "LogHTLCWithdraw(bytes32)";
+pub const LOG_HTLC_REFUND: &str = "LogHTLCRefund(bytes32)";
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut output = [0u8; 32];
+    let mut hasher = Keccak::v256();
+    hasher.update(data);
+    hasher.finalize(&mut output);
+    output
+}
+
+pub fn selector(signature: &str) -> [u8; 4] {
+    let mut selector = [0u8; 4];
+    selector.copy_from_slice(&keccak256(signature.as_bytes())[..4]);
+    selector
+}
+
+/// `keccak256(abi.encodePacked(sender, receiver, amount, hashlock, timelock))`,
+/// the id the EVM contract assigns on `newContract`.
+pub fn contract_id(
+    sender: Address,
+    receiver: Address,
+    amount: u128,
+    hashlock: [u8; 32],
+    timelock: u64,
+) -> [u8; 32] {
+    let mut data = Vec::with_capacity(20 + 20 + 32 * 3);
+    data.extend_from_slice(&sender);
+    data.extend_from_slice(&receiver);
+    data.extend_from_slice(&uint(amount));
+    data.extend_from_slice(&hashlock);
+    data.extend_from_slice(&uint(timelock.into()));
+    keccak256(&data)
+}
+
+/// Vara timelock in milliseconds to EVM seconds, rounded up so the EVM
+/// refund never opens before the Vara one.
+pub fn timelock_to_evm(timelock: u64) -> u64 {
+    timelock.div_ceil(1000)
+}
+
+/// EVM timelock in seconds to Vara milliseconds.
+pub fn timelock_from_evm(timelock: u64) -> Result<u64> {
+    timelock.checked_mul(1000).ok_or(Error::Overflow)
+}
+
+pub fn address_to_actor(address: Address) -> ActorId {
+    let mut bytes = [0u8; 32];
+    bytes[12..].copy_from_slice(&address);
+    ActorId::new(bytes)
+}
+
+/// The EVM address of an actor id, if it is a left-padded one.
+pub fn actor_to_address(actor: ActorId) -> Result<Address> {
+    let bytes: &[u8] = actor.as_ref();
+    word_to_address(bytes.try_into().map_err(|_| Error::InvalidAddress)?)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvmCall {
+    NewContract {
+        receiver: Address,
+        hashlock: [u8; 32],
+        timelock: u64,
+    },
+    Withdraw {
+        contract_id: [u8; 32],
+        preimage: [u8; 32],
+    },
+    Refund {
+        contract_id: [u8; 32],
+    },
+}
+
+impl EvmCall {
+    /// Call data: selector followed by the ABI-encoded arguments.
+    pub fn encode(&self) -> Vec<u8> {
+        let (signature, words) = match *self {
+            EvmCall::NewContract {
+                receiver,
+                hashlock,
+                timelock,
+            } => (
+                NEW_CONTRACT,
+                vec![address_word(receiver), hashlock, uint(timelock.into())],
+            ),
+            EvmCall::Withdraw {
+                contract_id,
+                preimage,
+            } => (WITHDRAW, vec![contract_id, preimage]),
+            EvmCall::Refund { contract_id } => (REFUND, vec![contract_id]),
+        };
+
+        let mut data = selector(signature).to_vec();
+        words.iter().for_each(|word| data.extend_from_slice(word));
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < 4 {
+            return Err(Error::InvalidLength);
+        }
+
+        let (head, args) = data.split_at(4);
+        let head: [u8; 4] = head.try_into().expect("4 bytes");
+        let words = words(args)?;
+
+        if head == selector(NEW_CONTRACT) {
+            let [receiver, hashlock, timelock] = words[..] else {
+                return Err(Error::InvalidLength);
+            };
+
+            Ok(EvmCall::NewContract {
+                receiver: word_to_address(receiver)?,
+                hashlock,
+                timelock: word_to_u64(timelock)?,
+            })
+        } else if head == selector(WITHDRAW) {
+            let [contract_id, preimage] = words[..] else {
+                return Err(Error::InvalidLength);
+            };
+
+            Ok(EvmCall::Withdraw {
+                contract_id,
+                preimage,
+            })
+        } else if head == selector(REFUND) {
+            let [contract_id] = words[..] else {
+                return Err(Error::InvalidLength);
+            };
+
+            Ok(EvmCall::Refund { contract_id })
+        } else {
+            Err(Error::UnknownSelector(head))
+        }
+    }
+
+    /// The EVM call doing what `action` does on Vara. The chains derive ids
+    /// differently, so `Redeem` and `Refund` target `contract_id`, the id the
+    /// EVM contract gave this leg (see [`contract_id`]). `Fund` ignores it.
+    pub fn from_action(action: &ExchangeAction, contract_id: [u8; 32]) -> Result<Self> {
+        match *action {
+            ExchangeAction::Fund(hashlock, timelock, receiver) => Ok(EvmCall::NewContract {
+                receiver: actor_to_address(receiver)?,
+                hashlock,
+                timelock: timelock_to_evm(timelock),
+            }),
+            ExchangeAction::Redeem(_, preimage) => Ok(EvmCall::Withdraw {
+                contract_id,
+                preimage,
+            }),
+            ExchangeAction::Refund(_) => Ok(EvmCall::Refund { contract_id }),
+            _ => Err(Error::Unsupported),
+        }
+    }
+
+    /// The Vara action doing what this call does on the EVM, acting on the
+    /// Vara leg `exchange_id`. `NewContract` ignores it.
+    pub fn to_action(&self, exchange_id: [u8; 32]) -> Result<ExchangeAction> {
+        Ok(match *self {
+            EvmCall::NewContract {
+                receiver,
+                hashlock,
+                timelock,
+            } => ExchangeAction::Fund(
+                hashlock,
+                timelock_from_evm(timelock)?,
+                address_to_actor(receiver),
+            ),
+            EvmCall::Withdraw { preimage, .. } => ExchangeAction::Redeem(exchange_id, preimage),
+            EvmCall::Refund { .. } => ExchangeAction::Refund(exchange_id),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvmEvent {
+    New {
+        contract_id: [u8; 32],
+        sender: Address,
+        receiver: Address,
+        amount: u128,
+        hashlock: [u8; 32],
+        timelock: u64,
+    },
+    Withdraw {
+        contract_id: [u8; 32],
+    },
+    Refund {
+        contract_id: [u8; 32],
+    },
+}
+
+impl EvmEvent {
+    /// `LogHTLCNew` for an exchange funded on Vara, in EVM units.
+    pub fn new_contract(contract_id: [u8; 32], exchange: &Exchange) -> Result<Self> {
+        Ok(EvmEvent::New {
+            contract_id,
+            sender: actor_to_address(exchange.sender)?,
+            receiver: actor_to_address(exchange.receiver)?,
+            amount: exchange.value,
+            hashlock: exchange.hashlock,
+            timelock: timelock_to_evm(exchange.timelock),
+        })
+    }
+
+    /// Log topics, starting with the event signature hash.
+    pub fn topics(&self) -> Vec<[u8; 32]> {
+        match *self {
+            EvmEvent::New {
+                contract_id,
+                sender,
+                receiver,
+                ..
+            } => vec![
+                keccak256(LOG_HTLC_NEW.as_bytes()),
+                contract_id,
+                address_word(sender),
+                address_word(receiver),
+            ],
+            EvmEvent::Withdraw { contract_id } => {
+                vec![keccak256(LOG_HTLC_WITHDRAW.as_bytes()), contract_id]
+            }
+            EvmEvent::Refund { contract_id } => {
+                vec![keccak256(LOG_HTLC_REFUND.as_bytes()), contract_id]
+            }
+        }
+    }
+
+    /// ABI-encoded non-indexed fields.
+    pub fn data(&self) -> Vec<u8> {
+        match *self {
+            EvmEvent::New {
+                amount,
+                hashlock,
+                timelock,
+                ..
+            } => [uint(amount), hashlock, uint(timelock.into())].concat(),
+            EvmEvent::Withdraw { .. } | EvmEvent::Refund { .. } => Vec::new(),
+        }
+    }
+
+    pub fn decode(topics: &[[u8; 32]], data: &[u8]) -> Result<Self> {
+        let signature = *topics.first().ok_or(Error::InvalidLength)?;
+        let words = words(data)?;
+
+        if signature == keccak256(LOG_HTLC_NEW.as_bytes()) {
+            let ([_, contract_id, sender, receiver], [amount, hashlock, timelock]) =
+                (topics, words.as_slice())
+            else {
+                return Err(Error::InvalidLength);
+            };
+
+            Ok(EvmEvent::New {
+                contract_id: *contract_id,
+                sender: word_to_address(*sender)?,
+                receiver: word_to_address(*receiver)?,
+                amount: word_to_uint(*amount)?,
+                hashlock: *hashlock,
+                timelock: word_to_u64(*timelock)?,
+            })
+        } else if signature == keccak256(LOG_HTLC_WITHDRAW.as_bytes()) {
+            match topics {
+                [_, contract_id] if words.is_empty() => Ok(EvmEvent::Withdraw {
+                    contract_id: *contract_id,
+                }),
+                _ => Err(Error::InvalidLength),
+            }
+        } else if signature == keccak256(LOG_HTLC_REFUND.as_bytes()) {
+            match topics {
+                [_, contract_id] if words.is_empty() => Ok(EvmEvent::Refund {
+                    contract_id: *contract_id,
+                }),
+                _ => Err(Error::InvalidLength),
+            }
+        } else {
+            Err(Error::UnknownEvent(signature))
+        }
+    }
+
+    /// `LogHTLCWithdraw` or `LogHTLCRefund` for a Vara leg settled by
+    /// `event`, logged under the EVM `contract_id` of the other leg. Use
+    /// [`EvmEvent::new_contract`] for funding, which needs the whole exchange.
+    pub fn settlement(event: &ExchangeEvent, contract_id: [u8; 32]) -> Result<Self> {
+        match *event {
+            ExchangeEvent::Redeemed(..) => Ok(EvmEvent::Withdraw { contract_id }),
+            ExchangeEvent::Refunded(_) => Ok(EvmEvent::Refund { contract_id }),
+            _ => Err(Error::Unsupported),
+        }
+    }
+
+    /// The matching exchange event for the Vara leg `exchange_id`. A withdraw
+    /// needs the `preimage` from its call data to become `Redeemed`.
+    pub fn to_exchange_event(
+        &self,
+        exchange_id: [u8; 32],
+        preimage: Option<[u8; 32]>,
+    ) -> Result<ExchangeEvent> {
+        Ok(match *self {
+            EvmEvent::New {
+                receiver, amount, ..
+            } => ExchangeEvent::Funded(exchange_id, address_to_actor(receiver), amount),
+            EvmEvent::Withdraw { .. } => {
+                ExchangeEvent::Redeemed(exchange_id, preimage.ok_or(Error::MissingPreimage)?)
+            }
+            EvmEvent::Refund { .. } => ExchangeEvent::Refunded(exchange_id),
+        })
+    }
+}
+
+fn uint(value: u128) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[16..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+fn word_to_uint(word: [u8; 32]) -> Result<u128> {
+    if word[..16].iter().any(|byte| *byte != 0) {
+        return Err(Error::Overflow);
+    }
+
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&word[16..]);
+    Ok(u128::from_be_bytes(bytes))
+}
+
+fn word_to_u64(word: [u8; 32]) -> Result<u64> {
+    word_to_uint(word)?.try_into().map_err(|_| Error::Overflow)
+}
+
+fn address_word(address: Address) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[12..].copy_from_slice(&address);
+    word
+}
+
+fn word_to_address(word: [u8; 32]) -> Result<Address> {
+    if word[..12].iter().any(|byte| *byte != 0) {
+        return Err(Error::InvalidAddress);
+    }
+
+    Ok(word[12..].try_into().expect("20 bytes"))
+}
+
+fn words(data: &[u8]) -> Result<Vec<[u8; 32]>> {
+    let (words, rest) = data.as_chunks::<32>();
+
+    if !rest.is_empty() {
+        return Err(Error::InvalidLength);
+    }
+
+    Ok(words.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SENDER: Address = [0xaa; 20];
+    const RECEIVER: Address = [0xbb; 20];
+    const HASHLOCK: [u8; 32] = [0x22; 32];
+    const PREIMAGE: [u8; 32] = [0x11; 32];
+    const AMOUNT: u128 = 1_000_000_000_000_000_000;
+    /// 2023-11-14T22:13:20Z, in seconds.
+    const TIMELOCK: u64 = 1_700_000_000;
+    /// [`contract_id`] of the values above.
+    const CONTRACT_ID: &str = "b524e08f86e3ee43ee2184c74b26b126af92fbfffd0da9ab0c642e5ed02daf38";
+    /// A Vara exchange id, unrelated to [`CONTRACT_ID`].
+    const EXCHANGE_ID: [u8; 32] = [0x33; 32];
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn contract() -> [u8; 32] {
+        hex(CONTRACT_ID).try_into().unwrap()
+    }
+
+    #[test]
+    fn keccak_vector() {
+        assert_eq!(
+            keccak256(b"").to_vec(),
+            hex("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470")
+        );
+    }
+
+    #[test]
+    fn selectors() {
+        assert_eq!(selector(NEW_CONTRACT), [0x33, 0x5e, 0xf5, 0xbd]);
+        assert_eq!(selector(WITHDRAW), [0x63, 0x61, 0x51, 0x49]);
+        assert_eq!(selector(REFUND), [0x72, 0x49, 0xfb, 0xb6]);
+    }
+
+    #[test]
+    fn event_topics() {
+        assert_eq!(
+            keccak256(LOG_HTLC_NEW.as_bytes()).to_vec(),
+            hex("329a8316ed9c3b2299597538371c2944c5026574e803b1ec31d6113e1cd67bde")
+        );
+        assert_eq!(
+            keccak256(LOG_HTLC_WITHDRAW.as_bytes()).to_vec(),
+            hex("d6fd4c8e45bf0c70693141c7ce46451b6a6a28ac8386fca2ba914044e0e23916")
+        );
+        assert_eq!(
+            keccak256(LOG_HTLC_REFUND.as_bytes()).to_vec(),
+            hex("989b3a845197c9aec15f8982bbb30b5da714050e662a7a287bb1a94c81e2e70e")
+        );
+    }
+
+    #[test]
+    fn contract_id_vector() {
+        assert_eq!(
+            contract_id(SENDER, RECEIVER, AMOUNT, HASHLOCK, TIMELOCK),
+            contract()
+        );
+    }
+
+    #[test]
+    fn new_contract_call() {
+        let call = EvmCall::NewContract {
+            receiver: RECEIVER,
+            hashlock: HASHLOCK,
+            timelock: TIMELOCK,
+        };
+        let data = hex(concat!(
+            "335ef5bd",
+            "000000000000000000000000bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+            "2222222222222222222222222222222222222222222222222222222222222222",
+            "000000000000000000000000000000000000000000000000000000006553f100",
+        ));
+
+        assert_eq!(call.encode(), data);
+        assert_eq!(EvmCall::decode(&data), Ok(call));
+    }
+
+    #[test]
+    fn withdraw_call() {
+        let call = EvmCall::Withdraw {
+            contract_id: contract(),
+            preimage: PREIMAGE,
+        };
+        let data = hex(concat!(
+            "63615149",
+            "b524e08f86e3ee43ee2184c74b26b126af92fbfffd0da9ab0c642e5ed02daf38",
+            "1111111111111111111111111111111111111111111111111111111111111111",
+        ));
+
+        assert_eq!(call.encode(), data);
+        assert_eq!(EvmCall::decode(&data), Ok(call));
+    }
+
+    #[test]
+    fn refund_call() {
+        let call = EvmCall::Refund {
+            contract_id: contract(),
+        };
+        let data = hex(concat!(
+            "7249fbb6",
+            "b524e08f86e3ee43ee2184c74b26b126af92fbfffd0da9ab0c642e5ed02daf38",
+        ));
+
+        assert_eq!(call.encode(), data);
+        assert_eq!(EvmCall::decode(&data), Ok(call));
+    }
+
+    #[test]
+    fn malformed_calls() {
+        assert_eq!(EvmCall::decode(&[0x72, 0x49]), Err(Error::InvalidLength));
+        assert_eq!(
+            EvmCall::decode(&[0xde, 0xad, 0xbe, 0xef]),
+            Err(Error::UnknownSelector([0xde, 0xad, 0xbe, 0xef]))
+        );
+        // `refund` with a truncated word.
+        assert_eq!(
+            EvmCall::decode(&hex("7249fbb6b524e08f")),
+            Err(Error::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn actions_keep_ids_apart() {
+        let redeem = ExchangeAction::Redeem(EXCHANGE_ID, PREIMAGE);
+        let call = EvmCall::from_action(&redeem, contract()).unwrap();
+
+        assert_eq!(
+            call,
+            EvmCall::Withdraw {
+                contract_id: contract(),
+                preimage: PREIMAGE,
+            }
+        );
+        assert_eq!(call.to_action(EXCHANGE_ID), Ok(redeem));
+
+        let refund = ExchangeAction::Refund(EXCHANGE_ID);
+        let call = EvmCall::from_action(&refund, contract()).unwrap();
+
+        assert_eq!(
+            call,
+            EvmCall::Refund {
+                contract_id: contract()
+            }
+        );
+        assert_eq!(call.to_action(EXCHANGE_ID), Ok(refund));
+    }
+
+    #[test]
+    fn fund_rounds_timelock_up() {
+        let fund = ExchangeAction::Fund(HASHLOCK, TIMELOCK * 1000 + 1, address_to_actor(RECEIVER));
+
+        assert_eq!(
+            EvmCall::from_action(&fund, [0; 32]),
+            Ok(EvmCall::NewContract {
+                receiver: RECEIVER,
+                hashlock: HASHLOCK,
+                timelock: TIMELOCK + 1,
+            })
+        );
+        assert_eq!(
+            EvmCall::from_action(
+                &ExchangeAction::Fund(HASHLOCK, 0, ActorId::new([0x01; 32])),
+                [0; 32]
+            ),
+            Err(Error::InvalidAddress)
+        );
+    }
+
+    #[test]
+    fn new_contract_event() {
+        let event = EvmEvent::New {
+            contract_id: contract(),
+            sender: SENDER,
+            receiver: RECEIVER,
+            amount: AMOUNT,
+            hashlock: HASHLOCK,
+            timelock: TIMELOCK,
+        };
+        let data = hex(concat!(
+            "0000000000000000000000000000000000000000000000000de0b6b3a7640000",
+            "2222222222222222222222222222222222222222222222222222222222222222",
+            "000000000000000000000000000000000000000000000000000000006553f100",
+        ));
+
+        assert_eq!(event.data(), data);
+        assert_eq!(event.topics()[1], contract());
+        assert_eq!(
+            event.topics()[2].to_vec(),
+            hex(concat!(
+                "000000000000000000000000",
+                "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+            ))
+        );
+        assert_eq!(EvmEvent::decode(&event.topics(), &data), Ok(event));
+        assert_eq!(
+            event.to_exchange_event(EXCHANGE_ID, None),
+            Ok(ExchangeEvent::Funded(
+                EXCHANGE_ID,
+                address_to_actor(RECEIVER),
+                AMOUNT
+            ))
+        );
+    }
+
+    #[test]
+    fn settlement_events() {
+        let redeemed = ExchangeEvent::Redeemed(EXCHANGE_ID, PREIMAGE);
+        let event = EvmEvent::settlement(&redeemed, contract()).unwrap();
+
+        assert_eq!(
+            event.topics(),
+            [keccak256(LOG_HTLC_WITHDRAW.as_bytes()), contract()]
+        );
+        assert!(event.data().is_empty());
+        assert_eq!(
+            event.to_exchange_event(EXCHANGE_ID, None),
+            Err(Error::MissingPreimage)
+        );
+        assert_eq!(
+            event.to_exchange_event(EXCHANGE_ID, Some(PREIMAGE)),
+            Ok(redeemed)
+        );
+
+        let refunded = ExchangeEvent::Refunded(EXCHANGE_ID);
+        let event = EvmEvent::settlement(&refunded, contract()).unwrap();
+
+        assert_eq!(EvmEvent::decode(&event.topics(), &[]), Ok(event));
+        assert_eq!(event.to_exchange_event(EXCHANGE_ID, None), Ok(refunded));
+    }
+}
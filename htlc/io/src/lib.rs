@@ -1,51 +1,464 @@
 #![no_std]
 
-use gmeta::{InOut, Metadata};
-use gstd::{prelude::*, ActorId};
+extern crate alloc;
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use runtime::{
+    codec::{Decode, Encode},
+    scale_info::TypeInfo,
+};
+use sha2::Digest;
+
+pub use runtime::ActorId;
+
+/// Crate the types build on: `gstd` for the program, `sails-rs` for the
+/// Sails service. Both re-export the same SCALE crates.
+#[cfg(feature = "gstd")]
+mod runtime {
+    pub use gstd::{codec, scale_info, ActorId};
+}
+
+#[cfg(not(feature = "gstd"))]
+mod runtime {
+    pub use sails_rs::{scale_codec as codec, scale_info, ActorId};
+}
 
 pub struct ExchangeMetadata;
 
-impl Metadata for ExchangeMetadata {
-    type Init = ();
-    type Handle = InOut<ExchangeAction, ExchangeEvent>;
-    type Others = ();
+#[cfg(feature = "gstd")]
+impl gmeta::Metadata for ExchangeMetadata {
+    type Init = gmeta::In<ExchangeInit>;
+    type Handle = gmeta::InOut<ExchangeAction, ExchangeEvent>;
+    type Others = gmeta::Out<ExchangeEventRecord>;
     type Reply = ();
     type Signal = ();
-    type State = InOut<[u8; 32], ExchangeState>;
+    type State = gmeta::InOut<ExchangeQuery, ExchangeReply>;
 }
 
-#[derive(Debug, Copy, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
-#[codec(crate = gstd::codec)]
-#[scale_info(crate = gstd::scale_info)]
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
 pub enum ExchangeAction {
     Fund([u8; 32], u64, ActorId),
     Redeem([u8; 32], [u8; 32]),
     Refund([u8; 32]),
+    /// Same as `Fund`, with extra per-exchange options.
+    FundWith([u8; 32], u64, ActorId, FundOptions),
+    /// Receiver only. Pays out whatever has vested since the last claim.
+    Claim([u8; 32]),
+    /// Owner only. Raises the dispute flag on a vesting exchange.
+    Dispute([u8; 32]),
+    /// Sender only. Takes back the unvested part of a disputed exchange.
+    Clawback([u8; 32]),
+    /// Sent by the program to itself at the timelock of exchanges funded
+    /// with a gas reservation, refunding them if still funded.
+    Expire([u8; 32]),
+    /// Owner only. Returns up to `limit` live exchanges after the cursor id
+    /// and freezes the program, so the export stays consistent. Only allowed
+    /// once the migration notice of an announced successor has passed.
+    Export(Option<[u8; 32]>, u32),
+    /// Owner only. Marks every live exchange of a frozen program as migrated
+    /// and sends their locked value to the announced successor, which must
+    /// have confirmed the import with `ConfirmImport`. The successor
+    /// reschedules automatic refunds with the gas of this message.
+    Retire(ActorId),
+    /// Owner only.
+    SetLimits(ExchangeLimits),
+    /// Owner only.
+    SetCompliance(ExchangeCompliance),
+    /// Owner only. Adds accounts to the allowlist.
+    Allow(Vec<ActorId>),
+    /// Owner only. Removes accounts from the allowlist.
+    Disallow(Vec<ActorId>),
+    /// Anyone may submit. Redeems with the receiver's signature over
+    /// [`redeem_payload`] and pays the exchange to the given address.
+    /// Not available for vesting exchanges.
+    RedeemWithSignature([u8; 32], [u8; 32], ActorId, RedeemSignature),
+    /// Sender only. Refunds before the timelock with the preimage of the
+    /// exchange's refund hashlock, which the receiver hands over to agree.
+    RefundWithSecret([u8; 32], [u8; 32]),
+    /// Sent by the announced successor from its `init` with the checksum of
+    /// the exchanges it imported. Must match this frozen program's export.
+    ConfirmImport([u8; 32]),
+    /// Sent by the predecessor on `Retire` with the locked value of the
+    /// exchanges imported under the checksum. Unfreezes the program and
+    /// schedules the automatic refunds the predecessor had scheduled.
+    SettleImport([u8; 32]),
+    /// Owner only. Names the program the exchanges will migrate to.
+    /// Counterparties get [`ExchangeInit::migration_notice`] to settle before
+    /// the export can start. Announcing again restarts the notice.
+    AnnounceSuccessor(ActorId),
 }
 
-#[derive(Debug, Copy, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
-#[codec(crate = gstd::codec)]
-#[scale_info(crate = gstd::scale_info)]
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
 pub enum ExchangeEvent {
     Funded([u8; 32], ActorId, u128),
     Redeemed([u8; 32], [u8; 32]),
     Refunded([u8; 32]),
+    Claimed([u8; 32], u128),
+    Disputed([u8; 32]),
+    ClawedBack([u8; 32], u128),
+    /// [`EXPORT_VERSION`], a page of live exchanges and the cursor of the
+    /// next page, if any.
+    Exported(u8, Vec<([u8; 32], Exchange)>, Option<[u8; 32]>),
+    /// Successor the locked value went to, and that value.
+    Retired(ActorId, u128),
+    /// Successor that confirmed importing this program's export.
+    ImportConfirmed(ActorId),
+    /// Locked value received from the predecessor.
+    ImportSettled(u128),
+    /// Announced successor and the block timestamp from which the program
+    /// can be exported.
+    SuccessorAnnounced(ActorId, u64),
+    LimitsSet(ExchangeLimits),
+    ComplianceSet(ExchangeCompliance),
+    Allowed(Vec<ActorId>),
+    Disallowed(Vec<ActorId>),
+}
+
+/// Every event the program replies with is also sent to `ActorId::zero()`
+/// wrapped in a record, so indexers can follow all activity.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub struct ExchangeEventRecord {
+    /// Starts at zero and grows by one per event, so gaps are detectable.
+    pub seq: u64,
+    pub timestamp: u64,
+    pub event: ExchangeEvent,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub struct ExchangeInit {
+    pub import: Option<ExchangeImport>,
+    pub limits: ExchangeLimits,
+    pub compliance: ExchangeCompliance,
+    /// Milliseconds between announcing a successor and exporting to it.
+    pub migration_notice: u64,
 }
 
+impl Default for ExchangeInit {
+    fn default() -> Self {
+        ExchangeInit {
+            import: None,
+            limits: ExchangeLimits::default(),
+            compliance: ExchangeCompliance::default(),
+            migration_notice: MIGRATION_NOTICE,
+        }
+    }
+}
+
+/// Per-sender exposure caps checked on `Fund`. `None` means unlimited.
+#[derive(Debug, Copy, Clone, Default, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub struct ExchangeLimits {
+    pub max_exchanges: Option<u32>,
+    pub max_locked_value: Option<u128>,
+}
+
+/// Which counterparties may trade, checked on `Fund`.
+#[derive(Debug, Copy, Clone, Default, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub enum ExchangeCompliance {
+    #[default]
+    Open,
+    /// Both sender and receiver must be on the program's allowlist.
+    Allowlist,
+    /// A registry program answers a [`ComplianceQuery`] with a `bool`.
+    Registry(ActorId),
+}
+
+/// Receiver's signature over [`redeem_payload`] wrapped by [`wrap_bytes`],
+/// made with the key behind the receiver's account. That is what wallets
+/// produce when asked to `signRaw` the payload.
+#[derive(Debug, Copy, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub enum RedeemSignature {
+    /// Signed under [`SR25519_SIGNING_CONTEXT`].
+    Sr25519([u8; 64]),
+    Ed25519([u8; 64]),
+}
+
+/// Message sent to the compliance registry. The registry replies `true`
+/// when `sender` may fund an exchange for `receiver`.
 #[derive(Debug, Copy, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
-#[codec(crate = gstd::codec)]
-#[scale_info(crate = gstd::scale_info)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub struct ComplianceQuery {
+    pub sender: ActorId,
+    pub receiver: ActorId,
+}
+
+/// Exchanges exported from a previous program version.
+///
+/// `version` must be [`EXPORT_VERSION`] and `checksum` must equal
+/// [`exchanges_checksum`] of `exchanges`. The program stays frozen until
+/// `predecessor` retires and sends their total locked value.
+#[derive(Debug, Clone, Default, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub struct ExchangeImport {
+    pub version: u8,
+    pub predecessor: ActorId,
+    pub exchanges: Vec<([u8; 32], Exchange)>,
+    pub checksum: [u8; 32],
+}
+
+#[derive(Debug, Copy, Clone, Default, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub struct FundOptions {
+    /// Release the value linearly over this many milliseconds after redeem
+    /// instead of all at once.
+    pub vesting: Option<u64>,
+    /// Gas to reserve out of the `FundWith` message's gas limit for the
+    /// automatic refund at the timelock. Programs cannot buy gas with the
+    /// value they hold, so the sender pays for it through a higher gas limit
+    /// rather than with attached value. The reservation is spent on the
+    /// delayed wake-up, which cannot be cancelled: whatever it leaves unused
+    /// goes back to the sender when it runs, even if the exchange settled
+    /// earlier.
+    pub reservation: Option<u64>,
+    /// Second hashlock, usually picked by the receiver. Its preimage lets the
+    /// sender refund before the timelock.
+    pub refund_hashlock: Option<[u8; 32]>,
+}
+
+#[derive(Debug, Copy, Clone, Default, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub struct Vesting {
+    pub duration: u64,
+    /// Block timestamp of the redeem, zero until the secret is revealed.
+    pub start: u64,
+    pub claimed: u128,
+    pub disputed: bool,
+}
+
+impl Vesting {
+    /// Part of `value` vested at `now`.
+    pub fn vested(&self, value: u128, now: u64) -> u128 {
+        let elapsed = now.saturating_sub(self.start);
+
+        if elapsed >= self.duration {
+            return value;
+        }
+
+        let (elapsed, duration) = (elapsed as u128, self.duration as u128);
+
+        (value / duration) * elapsed + (value % duration) * elapsed / duration
+    }
+}
+
+#[derive(Debug, Clone, Default, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub struct Exchange {
+    pub hashlock: [u8; 32],
+    pub timelock: u64,
+    pub value: u128,
+    pub sender: ActorId,
+    pub receiver: ActorId,
+    pub state: ExchangeState,
+    pub vesting: Option<Vesting>,
+    pub refund_hashlock: Option<[u8; 32]>,
+    /// Gas of the automatic refund scheduled at the timelock, if any. A
+    /// successor schedules the refund again with the same gas.
+    pub reservation: Option<u64>,
+}
+
+impl Exchange {
+    pub fn new(
+        hashlock: [u8; 32],
+        timelock: u64,
+        value: u128,
+        sender: ActorId,
+        receiver: ActorId,
+    ) -> Self {
+        Exchange {
+            hashlock,
+            timelock,
+            value,
+            sender,
+            receiver,
+            state: ExchangeState::Funded,
+            vesting: None,
+            refund_hashlock: None,
+            reservation: None,
+        }
+    }
+
+    /// Value the program still holds for this exchange.
+    pub fn outstanding(&self) -> u128 {
+        match (self.state, self.vesting) {
+            (ExchangeState::Funded, _) => self.value,
+            (ExchangeState::Redeemed, Some(vesting)) => self.value - vesting.claimed,
+            _ => 0,
+        }
+    }
+}
+
+/// Largest page `state` returns for a listing query.
+pub const MAX_PAGE: u32 = 100;
+
+/// Query answered by `state`.
+///
+/// Listings return exchanges in id order, starting after the cursor id, at
+/// most `limit` (capped at [`MAX_PAGE`]) per page.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub enum ExchangeQuery {
+    /// The whole [`ExchangeStorage`], as read by the `exchange-state`
+    /// metawasm functions.
+    All,
+    Exchange([u8; 32]),
+    /// Exchanges the account sends or receives.
+    ByParty(ActorId, Option<[u8; 32]>, u32),
+    ByState(ExchangeState, Option<[u8; 32]>, u32),
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub enum ExchangeReply {
+    Storage(ExchangeStorage),
+    /// `None` when no exchange has the id.
+    Exchange(Option<Exchange>),
+    /// A page of exchanges and the cursor of the next one, if any.
+    Exchanges(Vec<([u8; 32], Exchange)>, Option<[u8; 32]>),
+}
+
+impl ExchangeReply {
+    /// Storage of a reply to [`ExchangeQuery::All`], `None` for other replies.
+    pub fn into_storage(self) -> Option<ExchangeStorage> {
+        match self {
+            ExchangeReply::Storage(storage) => Some(storage),
+            _ => None,
+        }
+    }
+}
+
+/// Full program state, returned by `state` for [`ExchangeQuery::All`].
+#[derive(Debug, Clone, Default, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub struct ExchangeStorage {
+    pub exchanges: Vec<([u8; 32], Exchange)>,
+    pub stats: ExchangeStats,
+}
+
+/// Running totals kept by the program since its `init`.
+#[derive(Debug, Clone, Default, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub struct ExchangeStats {
+    pub funded: Volume,
+    /// Value paid out to receivers. Vesting exchanges add what vested at
+    /// redeem, then each claim, so value clawed back is left out.
+    pub redeemed: Volume,
+    /// Manual refunds and automatic ones at the timelock.
+    pub refunded: Volume,
+    /// Sum of the milliseconds from fund to redeem over `timed_redeems`.
+    pub redeem_time: u128,
+    /// Redeems of exchanges funded on this program. Imported exchanges
+    /// have no fund time and are left out of the average.
+    pub timed_redeems: u64,
+    pub receivers: BTreeMap<ActorId, ReceiverStats>,
+}
+
+impl ExchangeStats {
+    /// Mean time from fund to redeem, in milliseconds.
+    pub fn average_redeem_time(&self) -> Option<u64> {
+        if self.timed_redeems == 0 {
+            return None;
+        }
+
+        Some((self.redeem_time / self.timed_redeems as u128) as u64)
+    }
+
+    /// Share of settled exchanges that were redeemed, in basis points.
+    pub fn success_rate(&self) -> Option<u32> {
+        rate(self.redeemed.count, self.refunded.count)
+    }
+
+    /// Share of settled exchanges that were refunded, in basis points.
+    pub fn expiry_rate(&self) -> Option<u32> {
+        rate(self.refunded.count, self.redeemed.count)
+    }
+}
+
+/// Number and total value of a group of exchanges.
+#[derive(Debug, Copy, Clone, Default, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub struct Volume {
+    pub count: u64,
+    pub value: u128,
+}
+
+impl Volume {
+    pub fn add(&mut self, value: u128) {
+        self.count += 1;
+        self.add_value(value);
+    }
+
+    /// Adds value to exchanges already counted.
+    pub fn add_value(&mut self, value: u128) {
+        self.value = self.value.saturating_add(value);
+    }
+}
+
+/// How the exchanges funded for one receiver settled.
+#[derive(Debug, Copy, Clone, Default, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
+pub struct ReceiverStats {
+    pub redeemed: u64,
+    pub refunded: u64,
+}
+
+impl ReceiverStats {
+    /// Share of this receiver's settled exchanges that were redeemed, in
+    /// basis points.
+    pub fn success_rate(&self) -> Option<u32> {
+        rate(self.redeemed, self.refunded)
+    }
+}
+
+/// `part` out of `part + rest`, in basis points.
+fn rate(part: u64, rest: u64) -> Option<u32> {
+    let total = part as u128 + rest as u128;
+
+    if total == 0 {
+        return None;
+    }
+
+    Some((part as u128 * 10_000 / total) as u32)
+}
+
+#[derive(Debug, Copy, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
 pub enum ExchangeState {
     Invalid,
     Funded,
     Redeemed,
     Refunded,
     Expired,
+    Migrated,
 }
 
 #[derive(Debug, Copy, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
-#[codec(crate = gstd::codec)]
-#[scale_info(crate = gstd::scale_info)]
+#[codec(crate = runtime::codec)]
+#[scale_info(crate = runtime::scale_info)]
 pub enum ExchangeError {
     ZeroAmount,
     NotFutureTime,
@@ -54,6 +467,18 @@ pub enum ExchangeError {
     NotReceiverRedeem,
     NotSenderRefund,
     NotExpiredForRefund,
+    NotOwner,
+    Frozen,
+    InvalidChecksum,
+    TooManyExchanges,
+    LockedValueExceeded,
+    NotAllowed,
+    NotVesting,
+    NothingToClaim,
+    NotDisputed,
+    InvalidSignature,
+    NoRefundHashlock,
+    InvalidRefundSecret,
 }
 
 impl Default for ExchangeState {
@@ -61,3 +486,57 @@ impl Default for ExchangeState {
         ExchangeState::Invalid
     }
 }
+
+/// Version of the export format, bumped whenever the SCALE layout of
+/// [`Exchange`] changes. Imports of any other version are rejected.
+pub const EXPORT_VERSION: u8 = 2;
+
+/// Default [`ExchangeInit::migration_notice`]: one week.
+pub const MIGRATION_NOTICE: u64 = 7 * 24 * 60 * 60 * 1_000;
+
+/// Signing context of sr25519 redeem signatures, the one Substrate wallets
+/// sign under.
+pub const SR25519_SIGNING_CONTEXT: &[u8] = b"substrate";
+
+/// Prefix of [`redeem_payload`], so redeem signatures cannot pass for
+/// signatures over anything else.
+pub const REDEEM_DOMAIN: &[u8] = b"vara-htlc/redeem";
+
+/// Payload the receiver signs to authorise `RedeemWithSignature` on the
+/// exchange `program`. Binding the program keeps a signature from being
+/// replayed on another deployment holding the same exchange id.
+pub fn redeem_payload(
+    program: ActorId,
+    exchange_id: [u8; 32],
+    secret: [u8; 32],
+    payout: ActorId,
+) -> Vec<u8> {
+    let mut payload = REDEEM_DOMAIN.to_vec();
+    payload.extend((program, exchange_id, secret, payout).encode());
+    payload
+}
+
+/// `message` between `<Bytes>` and `</Bytes>`, the way wallets wrap raw data
+/// before signing it.
+pub fn wrap_bytes(message: &[u8]) -> Vec<u8> {
+    [b"<Bytes>", message, b"</Bytes>"].concat()
+}
+
+/// Id of the exchange `sender` funds for `receiver` under `hashlock`.
+pub fn exchange_id(sender: ActorId, receiver: ActorId, hashlock: [u8; 32]) -> [u8; 32] {
+    let mut data = [0u8; 96];
+    data[0..32].copy_from_slice(sender.as_ref());
+    data[32..64].copy_from_slice(receiver.as_ref());
+    data[64..96].copy_from_slice(&hashlock);
+
+    let mut output = [0u8; 32];
+    output.copy_from_slice(sha2::Sha256::digest(data).as_slice());
+    output
+}
+
+/// SHA-256 over the SCALE encoding of exported exchanges, in export order.
+pub fn exchanges_checksum(exchanges: &[([u8; 32], Exchange)]) -> [u8; 32] {
+    let mut output = [0u8; 32];
+    output.copy_from_slice(sha2::Sha256::digest(exchanges.encode()).as_slice());
+    output
+}
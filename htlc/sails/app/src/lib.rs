@@ -0,0 +1,340 @@
+#![no_std]
+
+//! Sails service over [`exchange_core::Exchanges`], the same logic the
+//! program runs. Only the runtime glue lives here.
+//!
+//! Exchanges migrate between Sails programs only. The import handshake
+//! (`ConfirmImport`, `SettleImport`) is sent as Sails routes, which the gstd
+//! program cannot decode, and this program takes nothing but routes. To
+//! move off the gstd program, announce a successor there so counterparties
+//! get the notice, let its exchanges settle or expire, and fund new ones
+//! here.
+
+extern crate alloc;
+
+use exchange_core::{expiry_delay, reschedule_gas, Env, Exchanges};
+use exchange_io::*;
+use sails_rs::{
+    gstd::{exec, msg, ReservationId},
+    prelude::*,
+};
+
+/// Route of [`ExchangeService`], as exposed by [`ExchangeProgram`].
+const SERVICE: &str = "Exchange";
+
+#[derive(Debug, Default)]
+struct Storage {
+    exchanges: Exchanges,
+    event_seq: u64,
+}
+
+static mut STORAGE: Option<Storage> = None;
+
+fn storage() -> &'static mut Storage {
+    unsafe { STORAGE.as_mut().expect("Program is not initialized") }
+}
+
+/// [`Env`] of the message being handled.
+struct SailsEnv;
+
+impl SailsEnv {
+    /// Reserves `gas` and sends `Expire(exchange_id)` from the reservation,
+    /// delayed by the blocks left until `timelock`.
+    fn delay_expiry(exchange_id: [u8; 32], timelock: u64, gas: u64) {
+        let delay: u32 = expiry_delay(exec::block_timestamp(), timelock)
+            .try_into()
+            .expect("Timelock too far for a delayed message");
+
+        // Spent right away: the delayed message holds the gas from here on.
+        let reservation = ReservationId::reserve(gas, 1).expect("Failed to reserve gas");
+
+        msg::send_bytes_delayed_from_reservation(
+            reservation,
+            exec::program_id(),
+            (SERVICE, "Expire", exchange_id).encode(),
+            0,
+            delay,
+        )
+        .expect("Failed to schedule expiry");
+    }
+}
+
+impl Env for SailsEnv {
+    fn source(&self) -> ActorId {
+        msg::source()
+    }
+
+    fn value(&self) -> u128 {
+        msg::value()
+    }
+
+    fn now(&self) -> u64 {
+        exec::block_timestamp()
+    }
+
+    fn program_id(&self) -> ActorId {
+        exec::program_id()
+    }
+
+    fn send(&mut self, to: ActorId, value: u128) {
+        msg::send(to, (), value).expect("Failed to send funds");
+    }
+
+    fn send_action(&mut self, to: ActorId, action: ExchangeAction, value: u128) {
+        let payload = match action {
+            ExchangeAction::ConfirmImport(checksum) => {
+                (SERVICE, "ConfirmImport", checksum).encode()
+            }
+            ExchangeAction::SettleImport(checksum) => (SERVICE, "SettleImport", checksum).encode(),
+            action => unreachable!("{action:?} is not sent between programs"),
+        };
+
+        msg::send_bytes(to, payload, value).expect("Failed to send message");
+    }
+
+    fn schedule_expiry(&mut self, exchange_id: [u8; 32], timelock: u64, gas: u64) {
+        SailsEnv::delay_expiry(exchange_id, timelock, gas);
+    }
+
+    fn reschedule_expiry(&mut self, exchange_id: [u8; 32], timelock: u64) {
+        SailsEnv::delay_expiry(exchange_id, timelock, reschedule_gas(exec::gas_available()));
+    }
+}
+
+/// Events of [`ExchangeService`]: the records the program broadcasts.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum ExchangeServiceEvent {
+    Record(ExchangeEventRecord),
+}
+
+/// Hashed time-locked exchanges of native value. Commands take the
+/// arguments of the matching [`ExchangeAction`] and return the event the
+/// program would reply with.
+#[derive(Default)]
+pub struct ExchangeService(());
+
+#[service(events = ExchangeServiceEvent)]
+impl ExchangeService {
+    pub fn new() -> Self {
+        ExchangeService(())
+    }
+
+    pub async fn fund(
+        &mut self,
+        hashlock: [u8; 32],
+        timelock: u64,
+        receiver: ActorId,
+        options: FundOptions,
+    ) -> ExchangeEvent {
+        check_compliance(msg::source(), receiver).await;
+
+        self.handle(ExchangeAction::FundWith(
+            hashlock, timelock, receiver, options,
+        ))
+    }
+
+    pub fn redeem(&mut self, exchange_id: [u8; 32], secret: [u8; 32]) -> ExchangeEvent {
+        self.handle(ExchangeAction::Redeem(exchange_id, secret))
+    }
+
+    pub fn redeem_with_signature(
+        &mut self,
+        exchange_id: [u8; 32],
+        secret: [u8; 32],
+        payout: ActorId,
+        signature: RedeemSignature,
+    ) -> ExchangeEvent {
+        self.handle(ExchangeAction::RedeemWithSignature(
+            exchange_id,
+            secret,
+            payout,
+            signature,
+        ))
+    }
+
+    pub fn refund(&mut self, exchange_id: [u8; 32]) -> ExchangeEvent {
+        self.handle(ExchangeAction::Refund(exchange_id))
+    }
+
+    pub fn refund_with_secret(
+        &mut self,
+        exchange_id: [u8; 32],
+        refund_secret: [u8; 32],
+    ) -> ExchangeEvent {
+        self.handle(ExchangeAction::RefundWithSecret(exchange_id, refund_secret))
+    }
+
+    /// Sent by the program to itself at the timelock of exchanges funded
+    /// with a gas reservation, refunding them if still funded.
+    pub fn expire(&mut self, exchange_id: [u8; 32]) {
+        assert!(
+            msg::source() == exec::program_id(),
+            "Only the program can expire exchanges"
+        );
+
+        if let Some(event) = storage().exchanges.expire(&mut SailsEnv, exchange_id) {
+            self.emit(event);
+        }
+    }
+
+    pub fn claim(&mut self, exchange_id: [u8; 32]) -> ExchangeEvent {
+        self.handle(ExchangeAction::Claim(exchange_id))
+    }
+
+    pub fn dispute(&mut self, exchange_id: [u8; 32]) -> ExchangeEvent {
+        self.handle(ExchangeAction::Dispute(exchange_id))
+    }
+
+    pub fn clawback(&mut self, exchange_id: [u8; 32]) -> ExchangeEvent {
+        self.handle(ExchangeAction::Clawback(exchange_id))
+    }
+
+    pub fn export(&mut self, cursor: Option<[u8; 32]>, limit: u32) -> ExchangeEvent {
+        self.handle(ExchangeAction::Export(cursor, limit))
+    }
+
+    pub fn retire(&mut self, successor: ActorId) -> ExchangeEvent {
+        self.handle(ExchangeAction::Retire(successor))
+    }
+
+    pub fn confirm_import(&mut self, checksum: [u8; 32]) -> ExchangeEvent {
+        self.handle(ExchangeAction::ConfirmImport(checksum))
+    }
+
+    pub fn settle_import(&mut self, checksum: [u8; 32]) -> ExchangeEvent {
+        self.handle(ExchangeAction::SettleImport(checksum))
+    }
+
+    pub fn announce_successor(&mut self, successor: ActorId) -> ExchangeEvent {
+        self.handle(ExchangeAction::AnnounceSuccessor(successor))
+    }
+
+    pub fn set_limits(&mut self, limits: ExchangeLimits) -> ExchangeEvent {
+        self.handle(ExchangeAction::SetLimits(limits))
+    }
+
+    pub fn set_compliance(&mut self, compliance: ExchangeCompliance) -> ExchangeEvent {
+        self.handle(ExchangeAction::SetCompliance(compliance))
+    }
+
+    pub fn allow(&mut self, accounts: Vec<ActorId>) -> ExchangeEvent {
+        self.handle(ExchangeAction::Allow(accounts))
+    }
+
+    pub fn disallow(&mut self, accounts: Vec<ActorId>) -> ExchangeEvent {
+        self.handle(ExchangeAction::Disallow(accounts))
+    }
+
+    /// Answers the same queries as the program's `state`.
+    pub fn query(&self, query: ExchangeQuery) -> ExchangeReply {
+        storage().exchanges.query(query)
+    }
+
+    pub fn exchange(&self, exchange_id: [u8; 32]) -> Option<Exchange> {
+        storage().exchanges.get(&exchange_id).cloned()
+    }
+
+    pub fn exchange_state(&self, exchange_id: [u8; 32]) -> ExchangeState {
+        storage()
+            .exchanges
+            .get(&exchange_id)
+            .map(|exchange| exchange.state)
+            .unwrap_or(ExchangeState::Invalid)
+    }
+
+    pub fn stats(&self) -> ExchangeStats {
+        storage().exchanges.stats().clone()
+    }
+
+    pub fn receiver_stats(&self, receiver: ActorId) -> ReceiverStats {
+        storage()
+            .exchanges
+            .stats()
+            .receivers
+            .get(&receiver)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn limits(&self) -> ExchangeLimits {
+        storage().exchanges.limits()
+    }
+
+    pub fn compliance(&self) -> ExchangeCompliance {
+        storage().exchanges.compliance()
+    }
+
+    pub fn is_allowed(&self, account: ActorId) -> bool {
+        storage().exchanges.is_allowed(&account)
+    }
+
+    pub fn owner(&self) -> ActorId {
+        storage().exchanges.owner()
+    }
+
+    pub fn frozen(&self) -> bool {
+        storage().exchanges.frozen()
+    }
+
+    fn handle(&mut self, action: ExchangeAction) -> ExchangeEvent {
+        let event = storage().exchanges.handle(&mut SailsEnv, action);
+
+        self.emit(event.clone());
+
+        event
+    }
+
+    fn emit(&mut self, event: ExchangeEvent) {
+        let storage = storage();
+
+        let record = ExchangeEventRecord {
+            seq: storage.event_seq,
+            timestamp: exec::block_timestamp(),
+            event,
+        };
+
+        self.notify_on(ExchangeServiceEvent::Record(record))
+            .expect("Failed to emit event");
+
+        storage.event_seq += 1;
+    }
+}
+
+pub struct ExchangeProgram(());
+
+#[program]
+impl ExchangeProgram {
+    /// An import stays frozen until the predecessor retires and settles it.
+    pub fn new(init: ExchangeInit) -> Self {
+        let storage = Storage {
+            exchanges: Exchanges::new(&mut SailsEnv, init),
+            ..Default::default()
+        };
+
+        unsafe { STORAGE = Some(storage) };
+
+        ExchangeProgram(())
+    }
+
+    pub fn exchange(&self) -> ExchangeService {
+        ExchangeService::new()
+    }
+}
+
+/// Panics unless the configured compliance policy lets `sender` fund an
+/// exchange for `receiver`.
+async fn check_compliance(sender: ActorId, receiver: ActorId) {
+    let Some(registry) = storage().exchanges.check_compliance(sender, receiver) else {
+        return;
+    };
+
+    let allowed: bool =
+        msg::send_for_reply_as(registry, ComplianceQuery { sender, receiver }, 0, 0)
+            .expect("Unable to query compliance registry")
+            .await
+            .expect("Unable to decode compliance registry reply");
+
+    assert!(allowed, "Counterparty not allowed");
+}
@@ -0,0 +1,37 @@
+use sails_client_gen::ClientGenerator;
+use std::{env, fs, path::PathBuf};
+
+/// Set to rewrite the committed IDL after changing the service.
+const UPDATE_IDL: &str = "EXCHANGE_UPDATE_IDL";
+
+fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR not set"));
+    let generated_path = out_dir.join("exchange.idl");
+    let idl_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("exchange.idl");
+
+    println!("cargo:rerun-if-changed={}", idl_path.display());
+    println!("cargo:rerun-if-env-changed={UPDATE_IDL}");
+
+    sails_idl_gen::generate_idl_to_file::<exchange_sails_app::ExchangeProgram>(&generated_path)
+        .expect("Failed to generate IDL");
+
+    // The committed IDL is what other languages build clients from, so a
+    // service change that leaves it stale fails the build.
+    let generated = fs::read_to_string(&generated_path).expect("Failed to read generated IDL");
+    let committed = fs::read_to_string(&idl_path).unwrap_or_default();
+
+    if generated != committed {
+        if env::var_os(UPDATE_IDL).is_none() {
+            panic!(
+                "{} is out of date with the service; rebuild with {UPDATE_IDL}=1 and commit it",
+                idl_path.display()
+            );
+        }
+
+        fs::write(&idl_path, &generated).expect("Failed to update IDL");
+    }
+
+    ClientGenerator::from_idl_path(&idl_path)
+        .generate_to(out_dir.join("exchange_client.rs"))
+        .expect("Failed to generate client");
+}
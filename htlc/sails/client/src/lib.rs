@@ -0,0 +1,9 @@
+#![no_std]
+
+//! Rust client for the Sails exchange program, generated from the IDL of
+//! its service.
+
+/// IDL of the exchange service, checked against the service at build time.
+pub const IDL: &str = include_str!("../exchange.idl");
+
+include!(concat!(env!("OUT_DIR"), "/exchange_client.rs"));
@@ -0,0 +1,285 @@
+use exchange_core::{expiry_delay, Env, Exchanges};
+use exchange_io::{ExchangeAction, ExchangeEvent, ExchangeInit};
+use gstd::ActorId;
+use std::{
+    collections::BTreeMap,
+    panic::{self, AssertUnwindSafe},
+};
+
+/// How a simulated chain keeps time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainConfig {
+    pub name: String,
+    /// Milliseconds between blocks. Messages run at block timestamps.
+    pub block_time: u64,
+    /// How far the chain's clock runs ahead of the reference clock, in
+    /// milliseconds. Negative values lag behind.
+    pub drift: i64,
+    /// Milliseconds from submitting a message to it being eligible for the
+    /// next block.
+    pub latency: u64,
+}
+
+impl ChainConfig {
+    pub fn new(name: impl Into<String>) -> Self {
+        ChainConfig {
+            name: name.into(),
+            block_time: 3_000,
+            drift: 0,
+            latency: 0,
+        }
+    }
+
+    pub fn block_time(mut self, block_time: u64) -> Self {
+        assert!(block_time > 0, "Block time must be greater than 0");
+        self.block_time = block_time;
+        self
+    }
+
+    pub fn drift(mut self, drift: i64) -> Self {
+        self.drift = drift;
+        self
+    }
+
+    pub fn latency(mut self, latency: u64) -> Self {
+        self.latency = latency;
+        self
+    }
+}
+
+/// One chain running the exchange logic, with its own ledger and clock.
+#[derive(Debug, Clone)]
+pub struct Chain {
+    config: ChainConfig,
+    program: ActorId,
+    exchanges: Exchanges,
+    balances: BTreeMap<ActorId, u128>,
+}
+
+/// Result of running one message on a chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Executed {
+    /// `None` for an `Expire` that found nothing to refund.
+    pub result: Result<Option<ExchangeEvent>, String>,
+    /// `Expire` messages to run at these chain timestamps.
+    pub expiries: Vec<([u8; 32], u64)>,
+}
+
+impl Chain {
+    pub(crate) fn new(config: ChainConfig, program: ActorId) -> Self {
+        Chain {
+            config,
+            program,
+            exchanges: Exchanges::default(),
+            balances: BTreeMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &ChainConfig {
+        &self.config
+    }
+
+    pub fn program(&self) -> ActorId {
+        self.program
+    }
+
+    pub fn exchanges(&self) -> &Exchanges {
+        &self.exchanges
+    }
+
+    pub fn balance(&self, account: ActorId) -> u128 {
+        self.balances.get(&account).copied().unwrap_or_default()
+    }
+
+    pub(crate) fn mint(&mut self, account: ActorId, value: u128) {
+        *self.balances.entry(account).or_default() += value;
+    }
+
+    /// Chain timestamp at reference time `time`.
+    pub fn chain_time(&self, time: u64) -> u64 {
+        time.saturating_add_signed(self.config.drift)
+    }
+
+    /// Reference time at chain timestamp `chain_time`.
+    pub fn reference_time(&self, chain_time: u64) -> u64 {
+        chain_time.saturating_add_signed(-self.config.drift)
+    }
+
+    /// Chain timestamp of the block that includes a message submitted at
+    /// reference time `time`.
+    pub fn inclusion_time(&self, time: u64) -> u64 {
+        self.next_block(self.chain_time(time + self.config.latency))
+    }
+
+    /// First block timestamp at or after `chain_time`.
+    pub fn next_block(&self, chain_time: u64) -> u64 {
+        chain_time.div_ceil(self.config.block_time) * self.config.block_time
+    }
+
+    pub(crate) fn init(&mut self, owner: ActorId, init: ExchangeInit, value: u128, now: u64) {
+        self.transfer(owner, self.program, value);
+
+        let mut env = SimEnv::new(self, owner, value, now);
+
+        self.exchanges = Exchanges::new(&mut env, init);
+    }
+
+    /// Runs `action` at chain timestamp `now`. A failed message leaves the
+    /// chain untouched and its value with the sender, as on Gear.
+    pub(crate) fn execute(
+        &mut self,
+        from: ActorId,
+        action: ExchangeAction,
+        value: u128,
+        now: u64,
+    ) -> Executed {
+        if self.balance(from) < value {
+            return Executed {
+                result: Err("Insufficient balance".into()),
+                expiries: Vec::new(),
+            };
+        }
+
+        let exchanges = self.exchanges.clone();
+
+        let mut env = SimEnv::new(self, from, value, now);
+
+        let result = catch(|| match action {
+            ExchangeAction::Expire(exchange_id) => {
+                assert!(
+                    from == self.program,
+                    "Only the program can expire exchanges"
+                );
+
+                self.exchanges.expire(&mut env, exchange_id)
+            }
+            action => {
+                if let ExchangeAction::Fund(_, _, receiver)
+                | ExchangeAction::FundWith(_, _, receiver, _) = action
+                {
+                    let registry = self.exchanges.check_compliance(from, receiver);
+
+                    assert!(
+                        registry.is_none(),
+                        "Compliance registries are not simulated"
+                    );
+                }
+
+                Some(self.exchanges.handle(&mut env, action))
+            }
+        });
+
+        let (sends, expiries) = (env.sends, env.expiries);
+
+        if result.is_err() {
+            self.exchanges = exchanges;
+
+            return Executed {
+                result,
+                expiries: Vec::new(),
+            };
+        }
+
+        self.transfer(from, self.program, value);
+
+        for (to, amount) in sends {
+            self.transfer(self.program, to, amount);
+        }
+
+        Executed { result, expiries }
+    }
+
+    fn transfer(&mut self, from: ActorId, to: ActorId, value: u128) {
+        if value == 0 {
+            return;
+        }
+
+        let balance = self.balances.entry(from).or_default();
+        *balance = balance
+            .checked_sub(value)
+            .expect("Program sent more than it holds");
+
+        *self.balances.entry(to).or_default() += value;
+    }
+}
+
+/// [`Env`] of a simulated message. Effects are collected and applied only
+/// if the message succeeds.
+struct SimEnv {
+    program: ActorId,
+    source: ActorId,
+    value: u128,
+    now: u64,
+    block_time: u64,
+    sends: Vec<(ActorId, u128)>,
+    expiries: Vec<([u8; 32], u64)>,
+}
+
+impl SimEnv {
+    fn new(chain: &Chain, source: ActorId, value: u128, now: u64) -> Self {
+        SimEnv {
+            program: chain.program,
+            source,
+            value,
+            now,
+            block_time: chain.config.block_time,
+            sends: Vec::new(),
+            expiries: Vec::new(),
+        }
+    }
+
+    /// Delays `Expire(exchange_id)` by as many blocks as the program would.
+    fn delay_expiry(&mut self, exchange_id: [u8; 32], timelock: u64) {
+        let blocks = expiry_delay(self.now, timelock);
+
+        self.expiries
+            .push((exchange_id, self.now + blocks * self.block_time));
+    }
+}
+
+impl Env for SimEnv {
+    fn source(&self) -> ActorId {
+        self.source
+    }
+
+    fn value(&self) -> u128 {
+        self.value
+    }
+
+    fn now(&self) -> u64 {
+        self.now
+    }
+
+    fn program_id(&self) -> ActorId {
+        self.program
+    }
+
+    fn send(&mut self, to: ActorId, value: u128) {
+        self.sends.push((to, value));
+    }
+
+    /// Migrations are not simulated: only the value moves.
+    fn send_action(&mut self, to: ActorId, _action: ExchangeAction, value: u128) {
+        self.sends.push((to, value));
+    }
+
+    /// Gas is not simulated.
+    fn schedule_expiry(&mut self, exchange_id: [u8; 32], timelock: u64, _gas: u64) {
+        self.delay_expiry(exchange_id, timelock);
+    }
+
+    fn reschedule_expiry(&mut self, exchange_id: [u8; 32], timelock: u64) {
+        self.delay_expiry(exchange_id, timelock);
+    }
+}
+
+/// Runs `f`, turning a panic into its message.
+fn catch<R>(f: impl FnOnce() -> R) -> Result<R, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Unknown panic".into())
+    })
+}
@@ -0,0 +1,326 @@
+//! Deterministic simulator for swap timing.
+//!
+//! Runs the exchange logic of `exchange-core` natively on any number of
+//! mock chains, each with its own block time, clock drift and inclusion
+//! latency. Scenarios are scripted as messages submitted at reference
+//! times, plus counterparties that react to revealed secrets. Messages run
+//! in reference time order and, within a block, in submission order, so a
+//! scenario always plays out the same way.
+
+mod chain;
+
+pub use chain::{Chain, ChainConfig, Executed};
+
+use exchange_client::Call;
+use exchange_io::{Exchange, ExchangeAction, ExchangeEvent, ExchangeInit, ExchangeState};
+use gstd::ActorId;
+use std::{collections::BTreeMap, fmt};
+
+/// A message handled by one of the chains.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Reference time the message ran at.
+    pub time: u64,
+    pub chain: usize,
+    /// Chain timestamp the message ran at.
+    pub chain_time: u64,
+    pub from: ActorId,
+    pub action: ExchangeAction,
+    pub result: Result<Option<ExchangeEvent>, String>,
+}
+
+/// Final state of one exchange.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExchangeReport {
+    pub chain: usize,
+    pub exchange_id: [u8; 32],
+    pub exchange: Exchange,
+}
+
+/// Everything that happened in a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub chains: Vec<String>,
+    pub log: Vec<Entry>,
+    pub exchanges: Vec<ExchangeReport>,
+}
+
+impl Report {
+    /// Final state of `exchange_id` on `chain`.
+    pub fn state(&self, chain: usize, exchange_id: [u8; 32]) -> ExchangeState {
+        self.exchanges
+            .iter()
+            .find(|report| report.chain == chain && report.exchange_id == exchange_id)
+            .map(|report| report.exchange.state)
+            .unwrap_or(ExchangeState::Invalid)
+    }
+
+    /// Messages that failed, with their error.
+    pub fn failures(&self) -> impl Iterator<Item = (&Entry, &str)> {
+        self.log.iter().filter_map(|entry| match &entry.result {
+            Err(error) => Some((entry, error.as_str())),
+            Ok(_) => None,
+        })
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.log {
+            let outcome = match &entry.result {
+                Ok(Some(event)) => describe(event),
+                Ok(None) => "no effect".into(),
+                Err(error) => format!("failed: {error}"),
+            };
+
+            writeln!(
+                f,
+                "{:>8} {:<12} {:>8} {} {}",
+                entry.time,
+                self.chains[entry.chain],
+                entry.chain_time,
+                short(entry.from.as_ref()),
+                outcome
+            )?;
+        }
+
+        for report in &self.exchanges {
+            writeln!(
+                f,
+                "{:<12} {} {:?} value {}",
+                self.chains[report.chain],
+                short(&report.exchange_id),
+                report.exchange.state,
+                report.exchange.value
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Counterparty that redeems on one chain once a secret shows up on another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Relay {
+    watch: (usize, [u8; 32]),
+    redeem: (usize, [u8; 32]),
+    party: ActorId,
+    delay: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Message {
+    chain: usize,
+    from: ActorId,
+    call: Call,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Simulator {
+    chains: Vec<Chain>,
+    /// Pending messages by reference time and submission order.
+    queue: BTreeMap<(u64, u64), Message>,
+    seq: u64,
+    time: u64,
+    relays: Vec<Relay>,
+    log: Vec<Entry>,
+}
+
+impl Simulator {
+    pub fn new() -> Self {
+        Simulator::default()
+    }
+
+    /// Adds a chain with an exchange deployed by `owner`, and returns its
+    /// index.
+    pub fn add_chain(&mut self, config: ChainConfig, owner: ActorId, init: ExchangeInit) -> usize {
+        let index = self.chains.len();
+        let mut program = [0xff; 32];
+        program[..8].copy_from_slice(&(index as u64).to_le_bytes());
+
+        let mut chain = Chain::new(config, ActorId::new(program));
+        let now = chain.chain_time(self.time);
+        chain.init(owner, init, 0, now);
+
+        self.chains.push(chain);
+
+        index
+    }
+
+    pub fn chain(&self, chain: usize) -> &Chain {
+        &self.chains[chain]
+    }
+
+    /// Current reference time.
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    pub fn mint(&mut self, chain: usize, account: ActorId, value: u128) {
+        self.chains[chain].mint(account, value);
+    }
+
+    /// Submits `call` from `from` to `chain` at reference time `time`.
+    pub fn submit(&mut self, time: u64, chain: usize, from: ActorId, call: Call) {
+        let time = time.max(self.time);
+        let included = self.chains[chain].inclusion_time(time);
+        let at = self.chains[chain].reference_time(included).max(time);
+
+        self.push(at, Message { chain, from, call });
+    }
+
+    /// Makes `party` redeem `redeem` on `redeem_chain` `delay` milliseconds
+    /// after the secret of `watch` is revealed on `watch_chain`.
+    pub fn relay(
+        &mut self,
+        (watch_chain, watch): (usize, [u8; 32]),
+        (redeem_chain, redeem): (usize, [u8; 32]),
+        party: ActorId,
+        delay: u64,
+    ) {
+        self.relays.push(Relay {
+            watch: (watch_chain, watch),
+            redeem: (redeem_chain, redeem),
+            party,
+            delay,
+        });
+    }
+
+    /// Runs every pending message, including the ones they cause.
+    pub fn run(&mut self) -> Report {
+        self.run_until(u64::MAX)
+    }
+
+    /// Runs pending messages up to reference time `until`.
+    pub fn run_until(&mut self, until: u64) -> Report {
+        while let Some(entry) = self.queue.first_entry() {
+            let (time, _) = *entry.key();
+
+            if time > until {
+                break;
+            }
+
+            let message = entry.remove();
+
+            self.time = time;
+            self.step(message);
+        }
+
+        if until != u64::MAX {
+            self.time = self.time.max(until);
+        }
+
+        self.report()
+    }
+
+    pub fn report(&self) -> Report {
+        Report {
+            chains: self
+                .chains
+                .iter()
+                .map(|chain| chain.config().name.clone())
+                .collect(),
+            log: self.log.clone(),
+            exchanges: self
+                .chains
+                .iter()
+                .enumerate()
+                .flat_map(|(index, chain)| {
+                    chain
+                        .exchanges()
+                        .exchanges()
+                        .map(move |(exchange_id, exchange)| ExchangeReport {
+                            chain: index,
+                            exchange_id: *exchange_id,
+                            exchange: exchange.clone(),
+                        })
+                })
+                .collect(),
+        }
+    }
+
+    fn push(&mut self, time: u64, message: Message) {
+        self.queue.insert((time, self.seq), message);
+        self.seq += 1;
+    }
+
+    fn step(&mut self, Message { chain, from, call }: Message) {
+        let target = &mut self.chains[chain];
+        let chain_time = target.chain_time(self.time);
+
+        let Executed { result, expiries } =
+            target.execute(from, call.action.clone(), call.value, chain_time);
+
+        for (exchange_id, chain_time) in expiries {
+            let target = &self.chains[chain];
+            let at = target.reference_time(target.next_block(chain_time));
+            let message = Message {
+                chain,
+                from: target.program(),
+                call: Call {
+                    action: ExchangeAction::Expire(exchange_id),
+                    value: 0,
+                },
+            };
+
+            self.push(at.max(self.time), message);
+        }
+
+        if let Ok(Some(ExchangeEvent::Redeemed(exchange_id, secret))) = result {
+            let relays: Vec<Relay> = self
+                .relays
+                .iter()
+                .filter(|relay| relay.watch == (chain, exchange_id))
+                .cloned()
+                .collect();
+
+            for relay in relays {
+                let (redeem_chain, redeem) = relay.redeem;
+                let call = Call {
+                    action: ExchangeAction::Redeem(redeem, secret),
+                    value: 0,
+                };
+
+                self.submit(self.time + relay.delay, redeem_chain, relay.party, call);
+            }
+        }
+
+        self.log.push(Entry {
+            time: self.time,
+            chain,
+            chain_time,
+            from,
+            action: call.action,
+            result,
+        });
+    }
+}
+
+/// Account numbered `id` the way gtest numbers them.
+pub fn actor(id: u64) -> ActorId {
+    let mut bytes = [0u8; 32];
+    bytes[..8].copy_from_slice(&id.to_le_bytes());
+    ActorId::new(bytes)
+}
+
+fn describe(event: &ExchangeEvent) -> String {
+    match event {
+        ExchangeEvent::Funded(exchange_id, receiver, value) => format!(
+            "Funded {} for {} value {value}",
+            short(exchange_id),
+            short(receiver.as_ref())
+        ),
+        ExchangeEvent::Redeemed(exchange_id, secret) => {
+            format!("Redeemed {} secret {}", short(exchange_id), short(secret))
+        }
+        ExchangeEvent::Refunded(exchange_id) => format!("Refunded {}", short(exchange_id)),
+        event => format!("{event:?}"),
+    }
+}
+
+fn short(bytes: &[u8]) -> String {
+    bytes[..4]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
@@ -0,0 +1,235 @@
+//! Rehearses timing races of a two-chain swap and prints what happened to
+//! each exchange:
+//!
+//! ```text
+//! simulate [late-redeem|clock-drift|collision]
+//! ```
+
+use exchange_client::{Call, Secret};
+use exchange_io::{exchange_id, ExchangeInit};
+use exchange_sim::{actor, ChainConfig, Simulator};
+use std::{env, panic};
+
+const OWNER: u64 = 1;
+const ALICE: u64 = 10;
+const BOB: u64 = 11;
+const VALUE: u128 = 1_000;
+
+type Scenario = fn() -> Swap;
+
+/// Alice locks on chain A for Bob with the long timelock, Bob locks on
+/// chain B for Alice with the short one, and Bob mirrors Alice's secret
+/// `relay_delay` milliseconds after she reveals it on chain B.
+struct Swap {
+    sim: Simulator,
+    a: usize,
+    b: usize,
+    secret: Secret,
+    timelock_a: u64,
+    timelock_b: u64,
+}
+
+impl Swap {
+    fn new(chain_b: ChainConfig, reserve_b: bool, relay_delay: u64) -> Self {
+        let (owner, alice, bob) = (actor(OWNER), actor(ALICE), actor(BOB));
+
+        let mut sim = Simulator::new();
+        let a = sim.add_chain(ChainConfig::new("vara"), owner, ExchangeInit::default());
+        let b = sim.add_chain(chain_b, owner, ExchangeInit::default());
+
+        sim.mint(a, alice, VALUE);
+        sim.mint(b, bob, VALUE);
+
+        let secret = Secret([7; 32]);
+        let hashlock = secret.hashlock();
+        let (timelock_a, timelock_b) = (120_000, 60_000);
+
+        sim.submit(
+            0,
+            a,
+            alice,
+            Call::fund(bob, hashlock, timelock_a).value(VALUE).build(),
+        );
+
+        let mut fund_b = Call::fund(alice, hashlock, timelock_b).value(VALUE);
+
+        if reserve_b {
+            fund_b = fund_b.reservation(1_000_000_000);
+        }
+
+        sim.submit(0, b, bob, fund_b.build());
+
+        sim.relay(
+            (b, exchange_id(bob, alice, hashlock)),
+            (a, exchange_id(alice, bob, hashlock)),
+            bob,
+            relay_delay,
+        );
+
+        Swap {
+            sim,
+            a,
+            b,
+            secret,
+            timelock_a,
+            timelock_b,
+        }
+    }
+
+    /// Alice's exchange on chain A.
+    fn id_a(&self) -> [u8; 32] {
+        exchange_id(actor(ALICE), actor(BOB), self.secret.hashlock())
+    }
+
+    /// Bob's exchange on chain B.
+    fn id_b(&self) -> [u8; 32] {
+        exchange_id(actor(BOB), actor(ALICE), self.secret.hashlock())
+    }
+
+    fn redeem_b(&mut self, time: u64) {
+        let call = Call::redeem(self.id_b(), self.secret);
+
+        self.sim.submit(time, self.b, actor(ALICE), call);
+    }
+
+    fn refund_a(&mut self, time: u64) {
+        let call = Call::refund(self.id_a());
+
+        self.sim.submit(time, self.a, actor(ALICE), call);
+    }
+
+    fn run(mut self) -> Self {
+        self.sim.run();
+        self
+    }
+}
+
+/// Bob mirrors the secret too slowly and Alice refunds first.
+fn late_redeem() -> Swap {
+    let mut swap = Swap::new(
+        ChainConfig::new("counterpart").latency(2_000),
+        false,
+        70_000,
+    );
+
+    swap.refund_a(swap.timelock_a);
+    swap.redeem_b(swap.timelock_b - 10_000);
+
+    swap.run()
+}
+
+/// Chain B's clock runs 30 seconds ahead, so its automatic refund fires
+/// before Alice's redeem, which she timed by the reference clock.
+fn clock_drift() -> Swap {
+    let chain_b = ChainConfig::new("counterpart").drift(30_000);
+    let mut swap = Swap::new(chain_b, true, 5_000);
+
+    swap.redeem_b(swap.timelock_b - 20_000);
+
+    swap.run()
+}
+
+/// Bob's mirrored redeem and Alice's refund land in the same block on
+/// chain A; the one submitted first wins.
+fn collision() -> Swap {
+    let mut swap = Swap::new(ChainConfig::new("counterpart"), false, 59_000);
+
+    swap.refund_a(swap.timelock_a);
+    swap.redeem_b(swap.timelock_b);
+
+    swap.run()
+}
+
+fn main() {
+    // Failed messages are expected; they show up in the report.
+    panic::set_hook(Box::new(|_| {}));
+
+    let scenarios: [(&str, Scenario); 3] = [
+        ("late-redeem", late_redeem),
+        ("clock-drift", clock_drift),
+        ("collision", collision),
+    ];
+
+    let only = env::args().nth(1);
+
+    for (name, scenario) in scenarios {
+        if only.as_deref().is_some_and(|only| only != name) {
+            continue;
+        }
+
+        println!("== {name}");
+        print!("{}", scenario().sim.report());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exchange_io::ExchangeState;
+
+    impl Swap {
+        fn states(&self) -> [ExchangeState; 2] {
+            let report = self.sim.report();
+
+            [
+                report.state(self.a, self.id_a()),
+                report.state(self.b, self.id_b()),
+            ]
+        }
+
+        /// Balance of `party` on chains A and B.
+        fn balances(&self, party: u64) -> [u128; 2] {
+            [
+                self.sim.chain(self.a).balance(actor(party)),
+                self.sim.chain(self.b).balance(actor(party)),
+            ]
+        }
+
+        fn failures(&self) -> Vec<(usize, String)> {
+            self.sim
+                .report()
+                .failures()
+                .map(|(entry, error)| (entry.chain, error.into()))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn late_redeem_leaves_bob_short() {
+        let swap = late_redeem();
+
+        assert_eq!(
+            swap.states(),
+            [ExchangeState::Refunded, ExchangeState::Redeemed]
+        );
+        assert_eq!(swap.balances(ALICE), [VALUE, VALUE]);
+        assert_eq!(swap.balances(BOB), [0, 0]);
+        assert_eq!(swap.failures(), [(swap.a, "Invalid exchange state".into())]);
+    }
+
+    #[test]
+    fn clock_drift_refunds_before_alice_redeems() {
+        let swap = clock_drift();
+
+        assert_eq!(
+            swap.states(),
+            [ExchangeState::Funded, ExchangeState::Refunded]
+        );
+        assert_eq!(swap.balances(ALICE), [0, 0]);
+        assert_eq!(swap.balances(BOB), [0, VALUE]);
+        assert_eq!(swap.failures(), [(swap.b, "Invalid exchange state".into())]);
+    }
+
+    #[test]
+    fn collision_goes_to_the_first_submitted() {
+        let swap = collision();
+
+        assert_eq!(
+            swap.states(),
+            [ExchangeState::Refunded, ExchangeState::Redeemed]
+        );
+        assert_eq!(swap.balances(ALICE), [VALUE, VALUE]);
+        assert_eq!(swap.balances(BOB), [0, 0]);
+        assert_eq!(swap.failures(), [(swap.a, "Invalid exchange state".into())]);
+    }
+}
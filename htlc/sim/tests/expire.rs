@@ -0,0 +1,57 @@
+use exchange_client::{Call, Secret};
+use exchange_io::{exchange_id, ExchangeAction, ExchangeEvent, ExchangeInit, ExchangeState};
+use exchange_sim::{actor, ChainConfig, Simulator};
+
+const VALUE: u128 = 1_000;
+const TIMELOCK: u64 = 60_000;
+
+/// Blocks three times faster than the program expects wake it up at a
+/// third of the time left, over and over, until the timelock passes.
+#[test]
+fn early_wake_up_waits_for_the_timelock() {
+    let (owner, alice, bob) = (actor(1), actor(10), actor(11));
+    let hashlock = Secret([7; 32]).hashlock();
+    let id = exchange_id(alice, bob, hashlock);
+
+    let mut sim = Simulator::new();
+    let chain = sim.add_chain(
+        ChainConfig::new("fast").block_time(1_000),
+        owner,
+        ExchangeInit::default(),
+    );
+
+    sim.mint(chain, alice, VALUE);
+    sim.submit(
+        0,
+        chain,
+        alice,
+        Call::fund(bob, hashlock, TIMELOCK)
+            .value(VALUE)
+            .reservation(1_000_000_000)
+            .build(),
+    );
+
+    let report = sim.run();
+
+    let wake_ups: Vec<_> = report
+        .log
+        .iter()
+        .filter(|entry| entry.action == ExchangeAction::Expire(id))
+        .collect();
+
+    assert_eq!(
+        wake_ups
+            .iter()
+            .map(|entry| entry.chain_time)
+            .collect::<Vec<_>>(),
+        [20_000, 34_000, 43_000, 49_000, 53_000, 56_000, 58_000, 59_000, 60_000]
+    );
+
+    let (last, early) = wake_ups.split_last().unwrap();
+
+    assert!(early.iter().all(|entry| entry.result == Ok(None)));
+    assert_eq!(last.result, Ok(Some(ExchangeEvent::Refunded(id))));
+
+    assert_eq!(report.state(chain, id), ExchangeState::Refunded);
+    assert_eq!(sim.chain(chain).balance(alice), VALUE);
+}
@@ -1,141 +1,159 @@
 #![no_std]
 
-use collections::HashMap;
+use exchange_core::{expiry_delay, reschedule_gas, Env, Exchanges};
 use exchange_io::*;
-use gstd::{exec, msg, prelude::*, ActorId};
-use sha2::Digest;
-
-#[derive(Debug, Clone, Default)]
-pub struct Exchange {
-    hashlock: [u8; 32],
-    timelock: u64,
-    value: u128,
-    sender: ActorId,
-    receiver: ActorId,
-    state: ExchangeState,
+use gstd::{exec, msg, ActorId, ReservationId};
+
+#[derive(Debug, Default)]
+struct Store {
+    exchanges: Exchanges,
+    event_seq: u64,
 }
 
-impl Exchange {
-    fn new(
-        hashlock: [u8; 32],
-        timelock: u64,
-        value: u128,
-        sender: ActorId,
-        receiver: ActorId,
-    ) -> Self {
-        Exchange {
-            hashlock,
-            timelock,
-            value,
-            sender,
-            receiver,
-            state: ExchangeState::Funded,
-        }
+impl Store {
+    fn emit(&mut self, event: ExchangeEvent) {
+        let record = ExchangeEventRecord {
+            seq: self.event_seq,
+            timestamp: exec::block_timestamp(),
+            event,
+        };
+
+        msg::send(ActorId::zero(), record, 0).expect("Failed to emit event");
+
+        self.event_seq += 1;
     }
 }
 
-#[derive(Debug, Clone, Default)]
-struct Exchanges {
-    exchanges: HashMap<[u8; 32], Exchange>,
+/// [`Env`] of the message being handled.
+struct GearEnv;
+
+impl GearEnv {
+    /// Reserves `gas` and sends `Expire(exchange_id)` from the reservation,
+    /// delayed by the blocks left until `timelock`.
+    fn delay_expiry(exchange_id: [u8; 32], timelock: u64, gas: u64) {
+        let delay: u32 = expiry_delay(exec::block_timestamp(), timelock)
+            .try_into()
+            .expect("Timelock too far for a delayed message");
+
+        // Spent right away: the delayed message holds the gas from here on.
+        let reservation = ReservationId::reserve(gas, 1).expect("Failed to reserve gas");
+
+        msg::send_delayed_from_reservation(
+            reservation,
+            exec::program_id(),
+            ExchangeAction::Expire(exchange_id),
+            0,
+            delay,
+        )
+        .expect("Failed to schedule expiry");
+    }
 }
 
-static mut STORE: Option<Exchanges> = None;
+impl Env for GearEnv {
+    fn source(&self) -> ActorId {
+        msg::source()
+    }
 
-#[no_mangle]
-extern "C" fn handle() {
-    let action: ExchangeAction = msg::load().expect("Could not load ExchangeAction");
+    fn value(&self) -> u128 {
+        msg::value()
+    }
 
-    let store = unsafe { STORE.get_or_insert_with(Exchanges::default) };
+    fn now(&self) -> u64 {
+        exec::block_timestamp()
+    }
 
-    let exchanges = &mut store.exchanges;
+    fn program_id(&self) -> ActorId {
+        exec::program_id()
+    }
 
-    let result = match action {
-        ExchangeAction::Fund(hashlock, timelock, receiver) => {
-            assert!(msg::value() > 0, "Amount must be greater than 0");
-            assert!(
-                timelock > exec::block_timestamp(),
-                "Unlock time must be in the future"
-            );
-            assert!(!receiver.is_zero(), "Invalid receiver address");
+    fn send(&mut self, to: ActorId, value: u128) {
+        msg::send(to, (), value).expect("Failed to send funds");
+    }
+
+    fn send_action(&mut self, to: ActorId, action: ExchangeAction, value: u128) {
+        msg::send(to, action, value).expect("Failed to send message");
+    }
 
-            let mut data = [0u8; 96];
-            data[0..32].copy_from_slice(msg::source().as_ref());
-            data[32..64].copy_from_slice(receiver.as_ref());
-            data[64..96].copy_from_slice(&hashlock);
+    fn schedule_expiry(&mut self, exchange_id: [u8; 32], timelock: u64, gas: u64) {
+        GearEnv::delay_expiry(exchange_id, timelock, gas);
+    }
+
+    fn reschedule_expiry(&mut self, exchange_id: [u8; 32], timelock: u64) {
+        GearEnv::delay_expiry(exchange_id, timelock, reschedule_gas(exec::gas_available()));
+    }
+}
 
-            let exchange_id = sha2_256(&data);
+static mut STORE: Option<Store> = None;
 
-            assert!(!exchanges.contains_key(&exchange_id), "Duplicate exchange",);
+#[no_mangle]
+extern "C" fn init() {
+    let init: ExchangeInit = msg::load().expect("Could not load ExchangeInit");
 
-            let sender = msg::source();
-            let value = msg::value();
+    let store = Store {
+        exchanges: Exchanges::new(&mut GearEnv, init),
+        ..Default::default()
+    };
 
-            let exchange = Exchange::new(hashlock, timelock, value, sender, receiver);
+    unsafe { STORE = Some(store) };
+}
 
-            exchanges.insert(exchange_id, exchange);
+#[gstd::async_main]
+async fn main() {
+    let action: ExchangeAction = msg::load().expect("Could not load ExchangeAction");
 
-            ExchangeEvent::Funded(exchange_id, receiver, value)
-        }
-        ExchangeAction::Redeem(exchange_id, secret) => {
-            let exchange = exchanges.get_mut(&exchange_id).expect("Exchange not found");
+    if let ExchangeAction::Fund(_, _, receiver) | ExchangeAction::FundWith(_, _, receiver, _) =
+        action
+    {
+        check_compliance(msg::source(), receiver).await;
+    }
 
-            assert!(
-                exchange.state == ExchangeState::Funded,
-                "Invalid exchange state"
-            );
-            assert!(
-                exchange.receiver == msg::source(),
-                "Only receiver can redeem"
-            );
-            assert!(exchange.hashlock == sha2_256(&secret), "Invalid secret");
+    let store = unsafe { STORE.get_or_insert_with(Store::default) };
 
-            msg::send(exchange.receiver, (), exchange.value).expect("Failed to send funds");
+    let mut env = GearEnv;
 
-            exchange.state = ExchangeState::Redeemed;
+    if let ExchangeAction::Expire(exchange_id) = action {
+        assert!(
+            msg::source() == exec::program_id(),
+            "Only the program can expire exchanges"
+        );
 
-            ExchangeEvent::Redeemed(exchange_id, secret)
+        if let Some(event) = store.exchanges.expire(&mut env, exchange_id) {
+            store.emit(event);
         }
-        ExchangeAction::Refund(exchange_id) => {
-            let exchange = exchanges.get_mut(&exchange_id).expect("Exchange not found");
-
-            assert!(
-                exchange.state == ExchangeState::Funded,
-                "Invalid exchange state"
-            );
-            assert!(
-                exchange.timelock >= exec::block_timestamp(),
-                "Exchange not expired"
-            );
-            assert!(exchange.sender == msg::source(), "Only sender can refund");
 
-            msg::send(exchange.sender, (), exchange.value).expect("Failed to send funds");
+        return;
+    }
 
-            exchange.state = ExchangeState::Refunded;
+    let result = store.exchanges.handle(&mut env, action);
 
-            ExchangeEvent::Refunded(exchange_id)
-        }
-    };
+    store.emit(result.clone());
 
     msg::reply(result, 0).expect("Failed to encode or reply with `Result<ExchangeEvent, Error>`");
 }
 
-#[no_mangle]
-extern "C" fn state() {
-    let exchange_id = msg::load_bytes().expect("Could not load exchange id bytes");
+/// Panics unless the configured compliance policy lets `sender` fund an
+/// exchange for `receiver`.
+async fn check_compliance(sender: ActorId, receiver: ActorId) {
+    let store = unsafe { STORE.get_or_insert_with(Store::default) };
 
-    let store = unsafe { STORE.get_or_insert_with(Exchanges::default) };
-
-    let exchanges = &mut store.exchanges;
+    let Some(registry) = store.exchanges.check_compliance(sender, receiver) else {
+        return;
+    };
 
-    let exchange = exchanges
-        .get(exchange_id.as_slice())
-        .expect("Exchange not found");
+    let allowed: bool =
+        msg::send_for_reply_as(registry, ComplianceQuery { sender, receiver }, 0, 0)
+            .expect("Unable to query compliance registry")
+            .await
+            .expect("Unable to decode compliance registry reply");
 
-    msg::reply(exchange.state, 0).expect("Failed to share state");
+    assert!(allowed, "Counterparty not allowed");
 }
 
-fn sha2_256(data: &[u8]) -> [u8; 32] {
-    let mut output = [0u8; 32];
-    output.copy_from_slice(sha2::Sha256::digest(data).as_slice());
-    output
+#[no_mangle]
+extern "C" fn state() {
+    let query: ExchangeQuery = msg::load().expect("Could not load ExchangeQuery");
+
+    let store = unsafe { STORE.get_or_insert_with(Store::default) };
+
+    msg::reply(store.exchanges.query(query), 0).expect("Failed to share state");
 }
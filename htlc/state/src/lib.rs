@@ -1,16 +1,66 @@
 #![no_std]
 
 use exchange_io::*;
-use gstd::{prelude::*, ActorId};
+use gstd::prelude::*;
 
 #[gmeta::metawasm]
 pub mod metafns {
-    pub type State = HashMap<[u8; 32], Exchange>;
+    /// Reply to [`ExchangeQuery::All`].
+    pub type State = ExchangeReply;
 
     pub fn get_exchange_state(state: State, exchange_id: [u8; 32]) -> ExchangeState {
-        state
-            .get(&exchange_id)
-            .map(|exchange| exchange.state)
+        super::storage(state)
+            .exchanges
+            .iter()
+            .find(|(id, _)| *id == exchange_id)
+            .map(|(_, exchange)| exchange.state)
             .unwrap_or(ExchangeState::Invalid)
     }
+
+    pub fn get_stats(state: State) -> ExchangeStats {
+        super::storage(state).stats
+    }
+
+    /// Funded, redeemed and refunded count and value.
+    pub fn get_volumes(state: State) -> (Volume, Volume, Volume) {
+        let stats = super::storage(state).stats;
+
+        (stats.funded, stats.redeemed, stats.refunded)
+    }
+
+    /// Redeemed and expired shares of settled exchanges, in basis points.
+    pub fn get_rates(state: State) -> (Option<u32>, Option<u32>) {
+        let stats = super::storage(state).stats;
+
+        (stats.success_rate(), stats.expiry_rate())
+    }
+
+    /// Mean milliseconds from fund to redeem.
+    pub fn get_average_redeem_time(state: State) -> Option<u64> {
+        super::storage(state).stats.average_redeem_time()
+    }
+
+    pub fn get_receiver_stats(state: State, receiver: ActorId) -> ReceiverStats {
+        super::storage(state)
+            .stats
+            .receivers
+            .get(&receiver)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Success rate of a receiver, in basis points.
+    pub fn get_receiver_success_rate(state: State, receiver: ActorId) -> Option<u32> {
+        super::storage(state)
+            .stats
+            .receivers
+            .get(&receiver)
+            .and_then(ReceiverStats::success_rate)
+    }
+}
+
+fn storage(state: ExchangeReply) -> ExchangeStorage {
+    state
+        .into_storage()
+        .expect("State is not a reply to `ExchangeQuery::All`")
 }
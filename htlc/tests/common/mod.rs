@@ -0,0 +1,111 @@
+//! Helpers shared by the program tests. Each test binary uses some of them.
+#![allow(dead_code)]
+
+use exchange_io::{
+    exchange_id, ExchangeAction, ExchangeEvent, ExchangeEventRecord, ExchangeInit, ExchangeQuery,
+    ExchangeReply, ExchangeStats,
+};
+use gstd::{codec::Decode, ActorId};
+use gtest::{Program, RunResult, System};
+use sha2::{Digest, Sha256};
+
+pub const OWNER: u64 = 1;
+pub const SENDER: u64 = 10;
+pub const RECEIVER: u64 = 11;
+pub const OTHER: u64 = 12;
+pub const VALUE: u128 = 10_000_000_000_000;
+
+/// Initialises the exchange from `OWNER` and gives the parties funds.
+pub fn deploy(system: &System, init: ExchangeInit) -> Program<'_> {
+    system.init_logger();
+
+    for party in [SENDER, RECEIVER, OTHER] {
+        system.mint_to(party, 1_000 * VALUE);
+    }
+
+    let program = Program::current_opt(system);
+    assert!(!program.send(OWNER, init).main_failed());
+
+    program
+}
+
+/// Funds an exchange from `sender` to `RECEIVER` locked by the hash of
+/// `secret`, and returns its id, or `None` if the program refused it.
+pub fn fund(
+    program: &Program,
+    sender: u64,
+    secret: [u8; 32],
+    timelock: u64,
+    value: u128,
+) -> Option<[u8; 32]> {
+    let hashlock = hashlock(secret);
+    let result = program.send_with_value(
+        sender,
+        ExchangeAction::Fund(hashlock, timelock, ActorId::from(RECEIVER)),
+        value,
+    );
+
+    if result.main_failed() {
+        return None;
+    }
+
+    Some(exchange_id(
+        ActorId::from(sender),
+        ActorId::from(RECEIVER),
+        hashlock,
+    ))
+}
+
+pub fn hashlock(secret: [u8; 32]) -> [u8; 32] {
+    Sha256::digest(secret).into()
+}
+
+pub fn actor(program: &Program) -> ActorId {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(program.id().as_ref());
+    ActorId::from(bytes)
+}
+
+/// Records the programs broadcast while handling `result`.
+pub fn records(result: &RunResult) -> Vec<ExchangeEventRecord> {
+    result
+        .log()
+        .iter()
+        .filter(|log| log.destination().as_ref() == ActorId::zero().as_ref())
+        .filter_map(|log| ExchangeEventRecord::decode(&mut log.payload()).ok())
+        .collect()
+}
+
+/// Events the programs broadcast while handling `result`.
+pub fn events(result: &RunResult) -> Vec<ExchangeEvent> {
+    records(result)
+        .into_iter()
+        .map(|record| record.event)
+        .collect()
+}
+
+pub fn reply(result: &RunResult, to: u64) -> Option<ExchangeEvent> {
+    let to = ActorId::from(to);
+
+    result
+        .log()
+        .iter()
+        .filter(|log| log.destination().as_ref() == to.as_ref())
+        .find_map(|log| ExchangeEvent::decode(&mut log.payload()).ok())
+}
+
+pub fn stats(program: &Program) -> ExchangeStats {
+    program
+        .read_state::<ExchangeReply, _>(ExchangeQuery::All)
+        .expect("Failed to read state")
+        .into_storage()
+        .expect("State is not a reply to `ExchangeQuery::All`")
+        .stats
+}
+
+/// Spends blocks until the block timestamp reaches `time`.
+pub fn wait_until(system: &System, time: u64) {
+    while system.block_timestamp() < time {
+        system.spend_blocks(1);
+    }
+}
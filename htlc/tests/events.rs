@@ -0,0 +1,91 @@
+mod common;
+
+use common::*;
+use exchange_io::{exchange_id, ExchangeAction, ExchangeEvent, ExchangeEventRecord, ExchangeInit};
+use gstd::ActorId;
+use gtest::System;
+
+#[test]
+fn every_transition_is_broadcast_in_order() {
+    let system = System::new();
+    let program = deploy(&system, ExchangeInit::default());
+
+    let secret = [1; 32];
+    let timelock = system.block_timestamp() + 60_000;
+    let mut history: Vec<ExchangeEventRecord> = Vec::new();
+
+    let result = program.send_with_value(
+        SENDER,
+        ExchangeAction::Fund(hashlock(secret), timelock, ActorId::from(RECEIVER)),
+        VALUE,
+    );
+    let funded = exchange_id(
+        ActorId::from(SENDER),
+        ActorId::from(RECEIVER),
+        hashlock(secret),
+    );
+
+    // The broadcast carries the same event the caller gets as a reply.
+    assert_eq!(
+        events(&result),
+        [ExchangeEvent::Funded(
+            funded,
+            ActorId::from(RECEIVER),
+            VALUE
+        )]
+    );
+    assert_eq!(reply(&result, SENDER), events(&result).pop());
+    history.extend(records(&result));
+
+    system.spend_blocks(3);
+
+    // Failed messages roll back, so they neither emit nor use up a number.
+    let result = program.send(RECEIVER, ExchangeAction::Redeem(funded, [2; 32]));
+    assert!(result.main_failed());
+    assert!(records(&result).is_empty());
+
+    let result = program.send(OTHER, ExchangeAction::Redeem(funded, secret));
+    assert!(result.main_failed());
+    assert!(records(&result).is_empty());
+
+    // Activity of others is broadcast too, not only replied to its caller.
+    let result = program.send(RECEIVER, ExchangeAction::Redeem(funded, secret));
+    assert_eq!(events(&result), [ExchangeEvent::Redeemed(funded, secret)]);
+    history.extend(records(&result));
+
+    system.spend_blocks(1);
+
+    let result = program.send_with_value(
+        OTHER,
+        ExchangeAction::Fund(hashlock([3; 32]), timelock, ActorId::from(RECEIVER)),
+        VALUE,
+    );
+    history.extend(records(&result));
+
+    let other = exchange_id(
+        ActorId::from(OTHER),
+        ActorId::from(RECEIVER),
+        hashlock([3; 32]),
+    );
+
+    let result = program.send(OTHER, ExchangeAction::Refund(other));
+    assert!(result.main_failed(), "refunded before the timelock");
+    assert!(records(&result).is_empty());
+
+    wait_until(&system, timelock);
+
+    let result = program.send(OTHER, ExchangeAction::Refund(other));
+    assert_eq!(events(&result), [ExchangeEvent::Refunded(other)]);
+    history.extend(records(&result));
+
+    assert_eq!(history.len(), 4);
+
+    for (seq, record) in history.iter().enumerate() {
+        assert_eq!(record.seq, seq as u64);
+    }
+
+    assert!(history
+        .windows(2)
+        .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+    assert!(history[0].timestamp < history[1].timestamp);
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use common::*;
+use exchange_io::{
+    ComplianceQuery, ExchangeAction, ExchangeCompliance, ExchangeInit, ExchangeLimits,
+};
+use gstd::{
+    codec::{Decode, Encode},
+    ActorId,
+};
+use gtest::{Program, System, WasmProgram};
+
+#[test]
+fn limits_cap_each_sender() {
+    let system = System::new();
+    let program = deploy(
+        &system,
+        ExchangeInit {
+            limits: ExchangeLimits {
+                max_exchanges: Some(2),
+                max_locked_value: Some(3 * VALUE),
+            },
+            ..Default::default()
+        },
+    );
+    let timelock = system.block_timestamp() + 60_000;
+
+    assert!(fund(&program, SENDER, [1; 32], timelock, 2 * VALUE).is_some());
+
+    // Would lock 4 * VALUE.
+    assert!(fund(&program, SENDER, [2; 32], timelock, 2 * VALUE).is_none());
+
+    let exchange_id = fund(&program, SENDER, [2; 32], timelock, VALUE).unwrap();
+
+    // Both caps are reached, for this sender only.
+    assert!(fund(&program, SENDER, [3; 32], timelock, 1).is_none());
+    assert!(fund(&program, OTHER, [3; 32], timelock, 3 * VALUE).is_some());
+
+    // Settling an exchange frees its share.
+    let result = program.send(RECEIVER, ExchangeAction::Redeem(exchange_id, [2; 32]));
+    assert!(!result.main_failed());
+
+    assert!(fund(&program, SENDER, [3; 32], timelock, VALUE).is_some());
+
+    // Lifting the caps is up to the owner.
+    let result = program.send(SENDER, ExchangeAction::SetLimits(ExchangeLimits::default()));
+    assert!(result.main_failed());
+
+    let result = program.send(OWNER, ExchangeAction::SetLimits(ExchangeLimits::default()));
+    assert!(!result.main_failed());
+
+    assert!(fund(&program, SENDER, [4; 32], timelock, 10 * VALUE).is_some());
+}
+
+#[test]
+fn allowlist_needs_both_parties() {
+    let system = System::new();
+    let program = deploy(
+        &system,
+        ExchangeInit {
+            compliance: ExchangeCompliance::Allowlist,
+            ..Default::default()
+        },
+    );
+    let timelock = system.block_timestamp() + 60_000;
+
+    assert!(fund(&program, SENDER, [1; 32], timelock, VALUE).is_none());
+
+    let result = program.send(SENDER, ExchangeAction::Allow(vec![ActorId::from(SENDER)]));
+    assert!(result.main_failed(), "changed by a non-owner");
+
+    let result = program.send(OWNER, ExchangeAction::Allow(vec![ActorId::from(SENDER)]));
+    assert!(!result.main_failed());
+
+    assert!(fund(&program, SENDER, [1; 32], timelock, VALUE).is_none());
+
+    let result = program.send(OWNER, ExchangeAction::Allow(vec![ActorId::from(RECEIVER)]));
+    assert!(!result.main_failed());
+
+    assert!(fund(&program, SENDER, [1; 32], timelock, VALUE).is_some());
+
+    let result = program.send(
+        OWNER,
+        ExchangeAction::Disallow(vec![ActorId::from(RECEIVER)]),
+    );
+    assert!(!result.main_failed());
+
+    assert!(fund(&program, SENDER, [2; 32], timelock, VALUE).is_none());
+}
+
+/// Registry allowing exchanges funded by one sender only.
+#[derive(Debug)]
+struct Registry {
+    sender: ActorId,
+}
+
+impl WasmProgram for Registry {
+    fn init(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        let query = ComplianceQuery::decode(&mut payload.as_slice()).map_err(|_| "Bad query")?;
+
+        Ok(Some((query.sender == self.sender).encode()))
+    }
+
+    fn handle_reply(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<u8>, &'static str> {
+        Ok(Vec::new())
+    }
+}
+
+#[test]
+fn registry_decides_who_may_fund() {
+    let system = System::new();
+
+    let registry = Program::mock(
+        &system,
+        Registry {
+            sender: ActorId::from(SENDER),
+        },
+    );
+    assert!(!registry.send(OWNER, ()).main_failed());
+
+    let program = deploy(
+        &system,
+        ExchangeInit {
+            compliance: ExchangeCompliance::Registry(actor(&registry)),
+            ..Default::default()
+        },
+    );
+    let timelock = system.block_timestamp() + 60_000;
+
+    assert!(fund(&program, SENDER, [1; 32], timelock, VALUE).is_some());
+    assert!(fund(&program, OTHER, [1; 32], timelock, VALUE).is_none());
+}
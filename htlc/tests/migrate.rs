@@ -0,0 +1,162 @@
+mod common;
+
+use common::*;
+use exchange_io::{
+    exchange_id, exchanges_checksum, ExchangeAction, ExchangeEvent, ExchangeImport, ExchangeInit,
+    FundOptions, EXPORT_VERSION,
+};
+use gstd::{codec::Encode, ActorId};
+use gtest::{Program, System};
+
+const NOTICE: u64 = 60_000;
+
+#[test]
+fn retire_pays_the_confirmed_successor() {
+    let system = System::new();
+    let predecessor = deploy(&system, init());
+
+    let secret = [7; 32];
+    let timelock = system.block_timestamp() + 600_000;
+    let exchange_id = fund(&predecessor, SENDER, secret, timelock, VALUE).unwrap();
+
+    let successor = Program::current_opt(&system);
+
+    // Counterparties get the notice before anything can be exported.
+    let result = predecessor.send(OWNER, ExchangeAction::Export(None, 10));
+    assert!(result.main_failed(), "exported without an announcement");
+
+    let result = predecessor.send(OWNER, ExchangeAction::AnnounceSuccessor(actor(&successor)));
+    let Some(ExchangeEvent::SuccessorAnnounced(_, exportable_at)) = reply(&result, OWNER) else {
+        panic!("expected the successor to be announced");
+    };
+    assert_eq!(exportable_at, system.block_timestamp() + NOTICE);
+
+    let result = predecessor.send(OWNER, ExchangeAction::Export(None, 10));
+    assert!(result.main_failed(), "exported during the notice");
+
+    wait_until(&system, exportable_at);
+
+    let mut import = export(&predecessor);
+
+    // Only the announced successor may confirm, whatever the checksum.
+    let result = predecessor.send(OWNER, ExchangeAction::ConfirmImport(import.checksum));
+    assert!(result.main_failed(), "confirmed by the owner");
+
+    // An import of another format version is rejected outright.
+    import.version += 1;
+    let rejected = Program::current_opt(&system);
+    let result = rejected.send(OWNER, init_import(import.clone()));
+    assert!(result.main_failed());
+    import.version = EXPORT_VERSION;
+
+    let result = successor.send(OWNER, init_import(import));
+    assert!(!result.main_failed());
+    assert!(events(&result).contains(&ExchangeEvent::ImportConfirmed(actor(&successor))));
+
+    // Frozen until the predecessor hands over the locked value.
+    let result = successor.send(RECEIVER, ExchangeAction::Redeem(exchange_id, secret));
+    assert!(result.main_failed());
+
+    let result = predecessor.send(OWNER, ExchangeAction::Retire(actor(&rejected)));
+    assert!(result.main_failed(), "retired to an unannounced program");
+
+    let result = predecessor.send(OWNER, ExchangeAction::Retire(actor(&successor)));
+    assert!(result.contains(&(
+        OWNER,
+        ExchangeEvent::Retired(actor(&successor), VALUE).encode()
+    )));
+    assert!(events(&result).contains(&ExchangeEvent::ImportSettled(VALUE)));
+
+    let result = successor.send(RECEIVER, ExchangeAction::Redeem(exchange_id, secret));
+    assert!(result.contains(&(
+        RECEIVER,
+        ExchangeEvent::Redeemed(exchange_id, secret).encode()
+    )));
+}
+
+#[test]
+fn successor_refunds_at_the_timelock() {
+    let system = System::new();
+    let predecessor = deploy(&system, init());
+
+    let hashlock = hashlock([8; 32]);
+    let timelock = system.block_timestamp() + 600_000;
+
+    let options = FundOptions {
+        reservation: Some(10_000_000_000),
+        ..Default::default()
+    };
+    let result = predecessor.send_with_value(
+        SENDER,
+        ExchangeAction::FundWith(hashlock, timelock, ActorId::from(RECEIVER), options),
+        VALUE,
+    );
+    assert!(!result.main_failed());
+
+    let exchange_id = exchange_id(ActorId::from(SENDER), ActorId::from(RECEIVER), hashlock);
+
+    let successor = Program::current_opt(&system);
+
+    let result = predecessor.send(OWNER, ExchangeAction::AnnounceSuccessor(actor(&successor)));
+    assert!(!result.main_failed());
+
+    wait_until(&system, system.block_timestamp() + NOTICE);
+
+    let import = export(&predecessor);
+    assert_eq!(import.exchanges[0].1.reservation, options.reservation);
+
+    assert!(!successor.send(OWNER, init_import(import)).main_failed());
+    assert!(!predecessor
+        .send(OWNER, ExchangeAction::Retire(actor(&successor)))
+        .main_failed());
+
+    // The predecessor's wake-up finds it retired; the successor scheduled
+    // its own when it settled the import.
+    let mut refunds = Vec::new();
+
+    while system.block_timestamp() < timelock + 60_000 {
+        for result in system.spend_blocks(1) {
+            refunds.extend(
+                events(&result)
+                    .into_iter()
+                    .filter(|event| *event == ExchangeEvent::Refunded(exchange_id)),
+            );
+        }
+    }
+
+    assert_eq!(refunds.len(), 1);
+
+    let result = successor.send(SENDER, ExchangeAction::Refund(exchange_id));
+    assert!(result.main_failed(), "refunded twice");
+}
+
+/// Init of a fresh program with a short migration notice.
+fn init() -> ExchangeInit {
+    ExchangeInit {
+        migration_notice: NOTICE,
+        ..Default::default()
+    }
+}
+
+fn init_import(import: ExchangeImport) -> ExchangeInit {
+    ExchangeInit {
+        import: Some(import),
+        ..init()
+    }
+}
+
+/// Exports every live exchange of `predecessor` in one page.
+fn export(predecessor: &Program) -> ExchangeImport {
+    let result = predecessor.send(OWNER, ExchangeAction::Export(None, 10));
+    let Some(ExchangeEvent::Exported(version, exchanges, None)) = reply(&result, OWNER) else {
+        panic!("expected a single export page");
+    };
+    assert_eq!(version, EXPORT_VERSION);
+
+    ExchangeImport {
+        version,
+        predecessor: actor(predecessor),
+        checksum: exchanges_checksum(&exchanges),
+        exchanges,
+    }
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use common::*;
+use exchange_io::{
+    ExchangeAction, ExchangeInit, ExchangeQuery, ExchangeReply, ExchangeState, MAX_PAGE,
+};
+use gstd::ActorId;
+use gtest::{Program, System};
+
+fn query(program: &Program, query: ExchangeQuery) -> ExchangeReply {
+    program.read_state(query).expect("Failed to read state")
+}
+
+/// Ids of every page of a listing, following the cursors from the start.
+fn pages(
+    program: &Program,
+    listing: impl Fn(Option<[u8; 32]>) -> ExchangeQuery,
+) -> Vec<Vec<[u8; 32]>> {
+    let mut pages = Vec::new();
+    let mut cursor = None;
+
+    loop {
+        let ExchangeReply::Exchanges(page, next) = query(program, listing(cursor)) else {
+            panic!("expected a page of exchanges");
+        };
+
+        pages.push(
+            page.into_iter()
+                .map(|(exchange_id, _)| exchange_id)
+                .collect(),
+        );
+
+        match next {
+            Some(next) => cursor = Some(next),
+            None => return pages,
+        }
+    }
+}
+
+#[test]
+fn listings_are_paged_in_id_order() {
+    let system = System::new();
+    let program = deploy(&system, ExchangeInit::default());
+    let timelock = system.block_timestamp() + 600_000;
+
+    for index in 0..MAX_PAGE + 5 {
+        let mut secret = [0; 32];
+        secret[..4].copy_from_slice(&index.to_le_bytes());
+
+        fund(&program, SENDER, secret, timelock, VALUE).unwrap();
+    }
+
+    fund(&program, OTHER, [1; 32], timelock, VALUE).unwrap();
+
+    // Larger limits are capped, so the listing takes two pages.
+    let by_sender = pages(&program, |cursor| {
+        ExchangeQuery::ByParty(ActorId::from(SENDER), cursor, u32::MAX)
+    });
+    assert_eq!(by_sender.len(), 2);
+    assert_eq!(by_sender[0].len(), MAX_PAGE as usize);
+    assert_eq!(by_sender[1].len(), 5);
+
+    let ids: Vec<_> = by_sender.concat();
+    assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+
+    // The receiver is a party to the exchanges of both senders.
+    let by_receiver = pages(&program, |cursor| {
+        ExchangeQuery::ByParty(ActorId::from(RECEIVER), cursor, 50)
+    });
+    assert_eq!(by_receiver.concat().len(), ids.len() + 1);
+    assert!(by_receiver.iter().all(|page| page.len() <= 50));
+
+    let by_other = pages(&program, |cursor| {
+        ExchangeQuery::ByParty(ActorId::from(OTHER), cursor, 10)
+    });
+    assert_eq!(by_other, [vec![id_of(OTHER, [1; 32])]]);
+
+    // A page that ends exactly at the last match has no next cursor.
+    let ExchangeReply::Exchanges(page, next) = query(
+        &program,
+        ExchangeQuery::ByParty(ActorId::from(OTHER), None, 1),
+    ) else {
+        panic!("expected a page of exchanges");
+    };
+    assert_eq!(page.len(), 1);
+    assert_eq!(next, None);
+}
+
+#[test]
+fn listings_filter_by_state() {
+    let system = System::new();
+    let program = deploy(&system, ExchangeInit::default());
+    let timelock = system.block_timestamp() + 600_000;
+
+    let secrets = [[1; 32], [2; 32], [3; 32], [4; 32], [5; 32]];
+
+    for secret in secrets {
+        fund(&program, SENDER, secret, timelock, VALUE).unwrap();
+    }
+
+    for secret in &secrets[..2] {
+        let result = program.send(
+            RECEIVER,
+            ExchangeAction::Redeem(id_of(SENDER, *secret), *secret),
+        );
+        assert!(!result.main_failed());
+    }
+
+    let redeemed = pages(&program, |cursor| {
+        ExchangeQuery::ByState(ExchangeState::Redeemed, cursor, 1)
+    });
+    assert_eq!(redeemed.len(), 2);
+
+    let mut expected: Vec<_> = secrets[..2]
+        .iter()
+        .map(|secret| id_of(SENDER, *secret))
+        .collect();
+    expected.sort();
+    assert_eq!(redeemed.concat(), expected);
+
+    let funded = pages(&program, |cursor| {
+        ExchangeQuery::ByState(ExchangeState::Funded, cursor, 2)
+    });
+    assert_eq!(funded.iter().map(Vec::len).collect::<Vec<_>>(), [2, 1]);
+
+    let refunded = pages(&program, |cursor| {
+        ExchangeQuery::ByState(ExchangeState::Refunded, cursor, 2)
+    });
+    assert_eq!(refunded, [Vec::<[u8; 32]>::new()]);
+}
+
+#[test]
+fn single_exchange_and_full_state() {
+    let system = System::new();
+    let program = deploy(&system, ExchangeInit::default());
+    let timelock = system.block_timestamp() + 600_000;
+
+    let exchange_id = fund(&program, SENDER, [1; 32], timelock, VALUE).unwrap();
+
+    let ExchangeReply::Exchange(Some(exchange)) =
+        query(&program, ExchangeQuery::Exchange(exchange_id))
+    else {
+        panic!("expected the exchange");
+    };
+    assert_eq!(exchange.state, ExchangeState::Funded);
+    assert_eq!(exchange.value, VALUE);
+    assert_eq!(exchange.timelock, timelock);
+
+    assert_eq!(
+        query(&program, ExchangeQuery::Exchange([0; 32])),
+        ExchangeReply::Exchange(None)
+    );
+
+    let storage = query(&program, ExchangeQuery::All).into_storage().unwrap();
+    assert_eq!(storage.exchanges, [(exchange_id, exchange)]);
+    assert_eq!(storage.stats.funded.count, 1);
+    assert_eq!(storage.stats.funded.value, VALUE);
+}
+
+/// Id of the exchange `sender` funded for `RECEIVER` with `secret`.
+fn id_of(sender: u64, secret: [u8; 32]) -> [u8; 32] {
+    exchange_io::exchange_id(
+        ActorId::from(sender),
+        ActorId::from(RECEIVER),
+        hashlock(secret),
+    )
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use common::*;
+use exchange_io::{exchange_id, ExchangeAction, ExchangeEvent, ExchangeInit, FundOptions};
+use gstd::{codec::Encode, ActorId};
+use gtest::System;
+
+#[test]
+fn refund_opens_at_timelock() {
+    let system = System::new();
+    let program = deploy(&system, ExchangeInit::default());
+
+    let hashlock = [1; 32];
+    let timelock = system.block_timestamp() + 60_000;
+
+    let result = program.send_with_value(
+        SENDER,
+        ExchangeAction::Fund(hashlock, timelock, ActorId::from(RECEIVER)),
+        VALUE,
+    );
+    assert!(!result.main_failed());
+
+    let exchange_id = exchange_id(ActorId::from(SENDER), ActorId::from(RECEIVER), hashlock);
+
+    let result = program.send(SENDER, ExchangeAction::Refund(exchange_id));
+    assert!(result.main_failed(), "refunded before the timelock");
+
+    wait_until(&system, timelock);
+
+    let result = program.send(SENDER, ExchangeAction::Refund(exchange_id));
+    assert!(result.contains(&(SENDER, ExchangeEvent::Refunded(exchange_id).encode())));
+}
+
+#[test]
+fn refund_secret_opens_refund_early() {
+    let system = System::new();
+    let program = deploy(&system, ExchangeInit::default());
+
+    let secret = [1; 32];
+    let refund_secret = [2; 32];
+    let timelock = system.block_timestamp() + 60_000;
+
+    let result = program.send_with_value(
+        SENDER,
+        ExchangeAction::FundWith(
+            hashlock(secret),
+            timelock,
+            ActorId::from(RECEIVER),
+            FundOptions {
+                refund_hashlock: Some(hashlock(refund_secret)),
+                ..Default::default()
+            },
+        ),
+        VALUE,
+    );
+    assert!(!result.main_failed());
+
+    let exchange_id = exchange_id(
+        ActorId::from(SENDER),
+        ActorId::from(RECEIVER),
+        hashlock(secret),
+    );
+
+    // Exchanges funded without a refund hashlock wait for the timelock.
+    let plain = fund(&program, SENDER, [3; 32], timelock, VALUE).unwrap();
+    let result = program.send(
+        SENDER,
+        ExchangeAction::RefundWithSecret(plain, refund_secret),
+    );
+    assert!(result.main_failed(), "refunded without a refund hashlock");
+
+    let result = program.send(
+        SENDER,
+        ExchangeAction::RefundWithSecret(exchange_id, secret),
+    );
+    assert!(result.main_failed(), "refunded with the redeem secret");
+
+    let result = program.send(
+        RECEIVER,
+        ExchangeAction::RefundWithSecret(exchange_id, refund_secret),
+    );
+    assert!(result.main_failed(), "refunded by the receiver");
+
+    let result = program.send(
+        SENDER,
+        ExchangeAction::RefundWithSecret(exchange_id, refund_secret),
+    );
+    assert!(result.contains(&(SENDER, ExchangeEvent::Refunded(exchange_id).encode())));
+    assert!(system.block_timestamp() < timelock);
+
+    let result = program.send(RECEIVER, ExchangeAction::Redeem(exchange_id, secret));
+    assert!(result.main_failed(), "redeemed after the refund");
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use common::*;
+use exchange_io::{exchange_id, ExchangeAction, ExchangeEvent, ExchangeInit, RedeemSignature};
+use gstd::{codec::Encode, ActorId};
+use gtest::{Program, System};
+use hex_literal::hex;
+
+/// Id the vectors below were signed for.
+const PROGRAM: u64 = 100;
+const SECRET: [u8; 32] = [5; 32];
+
+/// sr25519 key from the mini secret `[0x22; 32]`, expanded the Ed25519 way
+/// as Substrate wallets do.
+const SR25519_KEY: [u8; 32] =
+    hex!("a63d1427fd44c2e8876a2d724abc93fee5cff849ca7cdc4a906e381a189c8c04");
+/// `SR25519_KEY` over the redeem payload of its exchange on `PROGRAM`,
+/// paying out to `OTHER`.
+const SR25519_SIGNATURE: [u8; 64] = hex!(
+    "3215af79ac3e45fa11c9b5add9ceb2a2d63c8fe807b0e480dad79c8e3a63095c"
+    "613d51041cf00d7f32996fe6b4315f4346abce39daba937c300c7b477be16984"
+);
+
+/// ed25519 key from the seed `[0x33; 32]`.
+const ED25519_KEY: [u8; 32] =
+    hex!("17cb79fb2b4120f2b1ec65e4198d6e08b28e813feb01e4a400839b85e18080ce");
+/// `ED25519_KEY` over the redeem payload of its exchange on `PROGRAM`,
+/// paying out to `OTHER`.
+const ED25519_SIGNATURE: [u8; 64] = hex!(
+    "4f1fec48a17811cca841638acb9113114cbcca3740f3bf945bb1294e7dfe7d94"
+    "d1683719c22da8f580469f5594c1c4dfa2dab005244a92784a92dfb751b24a06"
+);
+
+#[test]
+fn sr25519_signature_redeems() {
+    redeems_with(SR25519_KEY, RedeemSignature::Sr25519(SR25519_SIGNATURE));
+}
+
+#[test]
+fn ed25519_signature_redeems() {
+    redeems_with(ED25519_KEY, RedeemSignature::Ed25519(ED25519_SIGNATURE));
+}
+
+#[test]
+fn signature_of_another_key_is_rejected() {
+    let system = System::new();
+    let program = deploy_at(&system, PROGRAM);
+    let exchange_id = fund_for(&system, &program, SR25519_KEY);
+
+    // Signatures of the ed25519 key, or checked under the wrong scheme.
+    for signature in [
+        RedeemSignature::Ed25519(ED25519_SIGNATURE),
+        RedeemSignature::Sr25519(ED25519_SIGNATURE),
+        RedeemSignature::Ed25519(SR25519_SIGNATURE),
+    ] {
+        let result = program.send(
+            OTHER,
+            ExchangeAction::RedeemWithSignature(
+                exchange_id,
+                SECRET,
+                ActorId::from(OTHER),
+                signature,
+            ),
+        );
+        assert!(result.main_failed());
+    }
+}
+
+#[test]
+fn signature_binds_the_payout_and_the_program() {
+    let system = System::new();
+    let program = deploy_at(&system, PROGRAM);
+    let elsewhere = deploy_at(&system, PROGRAM + 1);
+
+    let exchange_id = fund_for(&system, &program, SR25519_KEY);
+    assert_eq!(fund_for(&system, &elsewhere, SR25519_KEY), exchange_id);
+
+    let signature = RedeemSignature::Sr25519(SR25519_SIGNATURE);
+
+    let result = program.send(
+        OTHER,
+        ExchangeAction::RedeemWithSignature(exchange_id, SECRET, ActorId::from(SENDER), signature),
+    );
+    assert!(result.main_failed(), "redeemed to another payout");
+
+    let result = elsewhere.send(
+        OTHER,
+        ExchangeAction::RedeemWithSignature(exchange_id, SECRET, ActorId::from(OTHER), signature),
+    );
+    assert!(result.main_failed(), "replayed on another deployment");
+}
+
+/// Redeems the exchange funded for `key` with `signature`, then replays it.
+fn redeems_with(key: [u8; 32], signature: RedeemSignature) {
+    let system = System::new();
+    let program = deploy_at(&system, PROGRAM);
+    let exchange_id = fund_for(&system, &program, key);
+
+    let action =
+        ExchangeAction::RedeemWithSignature(exchange_id, SECRET, ActorId::from(OTHER), signature);
+
+    // Anyone may relay the signature.
+    let result = program.send(SENDER, action.clone());
+    assert!(result.contains(&(
+        SENDER,
+        ExchangeEvent::Redeemed(exchange_id, SECRET).encode()
+    )));
+
+    let result = program.send(SENDER, action);
+    assert!(result.main_failed(), "replayed after the redeem");
+}
+
+/// Initialises the exchange under the id `id`, since the signed payload
+/// binds the program.
+fn deploy_at(system: &System, id: u64) -> Program<'_> {
+    system.init_logger();
+    system.mint_to(SENDER, 1_000 * VALUE);
+
+    let program = Program::current_with_id(system, id);
+    assert!(!program.send(OWNER, ExchangeInit::default()).main_failed());
+
+    program
+}
+
+/// Funds an exchange from `SENDER` to the account of `key`.
+fn fund_for(system: &System, program: &Program, key: [u8; 32]) -> [u8; 32] {
+    let receiver = ActorId::from(key);
+    let timelock = system.block_timestamp() + 600_000;
+
+    let result = program.send_with_value(
+        SENDER,
+        ExchangeAction::Fund(hashlock(SECRET), timelock, receiver),
+        VALUE,
+    );
+    assert!(!result.main_failed());
+
+    exchange_id(ActorId::from(SENDER), receiver, hashlock(SECRET))
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use common::*;
+use exchange_io::{exchange_id, ExchangeAction, ExchangeEvent, ExchangeInit, FundOptions, Volume};
+use gstd::ActorId;
+use gtest::{Program, System};
+
+const DURATION: u64 = 100_000;
+
+/// Funds a vesting exchange for `RECEIVER` and redeems it right away.
+fn redeemed(system: &System) -> (Program<'_>, [u8; 32]) {
+    let program = deploy(system, ExchangeInit::default());
+
+    let secret = [1; 32];
+    let timelock = system.block_timestamp() + 60_000;
+
+    let result = program.send_with_value(
+        SENDER,
+        ExchangeAction::FundWith(
+            hashlock(secret),
+            timelock,
+            ActorId::from(RECEIVER),
+            FundOptions {
+                vesting: Some(DURATION),
+                ..Default::default()
+            },
+        ),
+        VALUE,
+    );
+    assert!(!result.main_failed());
+
+    let exchange_id = exchange_id(
+        ActorId::from(SENDER),
+        ActorId::from(RECEIVER),
+        hashlock(secret),
+    );
+
+    let result = program.send(RECEIVER, ExchangeAction::Redeem(exchange_id, secret));
+    assert_eq!(
+        reply(&result, RECEIVER),
+        Some(ExchangeEvent::Redeemed(exchange_id, secret))
+    );
+
+    (program, exchange_id)
+}
+
+fn claim(program: &Program, exchange_id: [u8; 32]) -> Option<u128> {
+    match reply(
+        &program.send(RECEIVER, ExchangeAction::Claim(exchange_id)),
+        RECEIVER,
+    ) {
+        Some(ExchangeEvent::Claimed(_, amount)) => Some(amount),
+        _ => None,
+    }
+}
+
+#[test]
+fn claims_add_up_to_the_value() {
+    let system = System::new();
+    let (program, exchange_id) = redeemed(&system);
+    let start = system.block_timestamp();
+
+    wait_until(&system, start + DURATION / 2);
+
+    let first = claim(&program, exchange_id).unwrap();
+    assert!(first > 0 && first < VALUE);
+
+    wait_until(&system, start + DURATION);
+
+    let rest = claim(&program, exchange_id).unwrap();
+    assert_eq!(first + rest, VALUE);
+
+    assert_eq!(claim(&program, exchange_id), None, "claimed twice");
+}
+
+#[test]
+fn clawback_takes_only_the_unvested_part() {
+    let system = System::new();
+    let (program, exchange_id) = redeemed(&system);
+    let start = system.block_timestamp();
+
+    wait_until(&system, start + DURATION / 4);
+
+    let claimed = claim(&program, exchange_id).unwrap();
+
+    let result = program.send(SENDER, ExchangeAction::Clawback(exchange_id));
+    assert!(result.main_failed(), "clawed back without a dispute");
+
+    let result = program.send(SENDER, ExchangeAction::Dispute(exchange_id));
+    assert!(result.main_failed(), "disputed by the sender");
+
+    let result = program.send(OWNER, ExchangeAction::Dispute(exchange_id));
+    assert_eq!(
+        reply(&result, OWNER),
+        Some(ExchangeEvent::Disputed(exchange_id))
+    );
+
+    wait_until(&system, start + DURATION / 2);
+
+    let result = program.send(RECEIVER, ExchangeAction::Clawback(exchange_id));
+    assert!(result.main_failed(), "clawed back by the receiver");
+
+    let result = program.send(SENDER, ExchangeAction::Clawback(exchange_id));
+    let Some(ExchangeEvent::ClawedBack(_, clawed_back)) = reply(&result, SENDER) else {
+        panic!("expected a clawback");
+    };
+    assert!(clawed_back > 0);
+
+    // What vested up to the clawback stays claimable, and nothing after it.
+    let vested = claim(&program, exchange_id).unwrap();
+    assert_eq!(claimed + vested + clawed_back, VALUE);
+
+    // Only what reached the receiver counts as redeemed.
+    assert_eq!(
+        stats(&program).redeemed,
+        Volume {
+            count: 1,
+            value: claimed + vested,
+        }
+    );
+
+    wait_until(&system, start + DURATION);
+
+    assert_eq!(claim(&program, exchange_id), None);
+
+    let result = program.send(SENDER, ExchangeAction::Clawback(exchange_id));
+    assert!(result.main_failed(), "clawed back twice");
+}
@@ -0,0 +1,261 @@
+//! Mirrors secrets across the legs of HTLC swaps.
+//!
+//! When the counterparty redeems the exchange the user funded, the revealed
+//! secret is used to redeem the exchange funded for the user on the other
+//! leg. Funded exchanges that expire unredeemed are refunded.
+//!
+//! Each leg is read through an [`EventFeed`] of everything its program
+//! broadcasts, so swaps are followed whoever acts on them. Failed calls are
+//! retried with exponential backoff, up to [`Retry::attempts`] times.
+
+mod store;
+
+pub use store::{Leg, PendingSwap, Store, SwapStatus};
+
+use exchange_client::{Call, EventFeed, Secret, Transport};
+use exchange_io::ExchangeEvent;
+use gstd::ActorId;
+use std::{collections::BTreeMap, fmt, io};
+
+#[derive(Debug)]
+pub enum Error {
+    Store(io::Error),
+    Client(exchange_client::Error),
+    /// A swap refers to a program that is not registered as a leg.
+    UnknownLeg(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Store(error) => write!(f, "store error: {error}"),
+            Error::Client(error) => write!(f, "{error}"),
+            Error::UnknownLeg(program) => write!(f, "unknown leg `{program}`"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Store(error)
+    }
+}
+
+impl From<exchange_client::Error> for Error {
+    fn from(error: exchange_client::Error) -> Self {
+        Error::Client(error)
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// What a [`Watchtower::tick`] did.
+#[derive(Debug)]
+pub enum Report {
+    SecretRevealed([u8; 32]),
+    Redeemed([u8; 32]),
+    Refunded([u8; 32]),
+    Missed([u8; 32]),
+    /// The call failed and is retried once the backoff has passed.
+    Failed([u8; 32], exchange_client::Error),
+    /// The call failed too many times and is no longer retried.
+    GaveUp([u8; 32]),
+}
+
+/// How failed redeems and refunds are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    /// Calls made before giving up on a swap.
+    pub attempts: u32,
+    /// Milliseconds waited after the first failure, doubled after each
+    /// further one.
+    pub backoff: u64,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry {
+            attempts: 5,
+            backoff: 6_000,
+        }
+    }
+}
+
+impl Retry {
+    /// Milliseconds to wait after the `attempts`th failure.
+    fn delay(&self, attempts: u32) -> u64 {
+        self.backoff
+            .saturating_mul(1 << attempts.saturating_sub(1).min(32))
+    }
+}
+
+pub struct Watchtower<T> {
+    user: ActorId,
+    store: Store,
+    legs: BTreeMap<String, T>,
+    retry: Retry,
+}
+
+impl<T: Transport + EventFeed> Watchtower<T> {
+    /// Watches the swaps in `store` and acts as `user` on every leg.
+    pub fn new(user: ActorId, store: Store) -> Self {
+        Watchtower {
+            user,
+            store,
+            legs: BTreeMap::new(),
+            retry: Retry::default(),
+        }
+    }
+
+    pub fn set_retry(&mut self, retry: Retry) {
+        self.retry = retry;
+    }
+
+    pub fn add_leg(&mut self, program: impl Into<String>, leg: T) {
+        self.legs.insert(program.into(), leg);
+    }
+
+    pub fn leg_mut(&mut self, program: &str) -> Option<&mut T> {
+        self.legs.get_mut(program)
+    }
+
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// Starts watching `swap` and persists it.
+    pub fn watch(&mut self, swap: PendingSwap) -> Result<()> {
+        for program in [&swap.funded.program, &swap.claim.program] {
+            if !self.legs.contains_key(program) {
+                return Err(Error::UnknownLeg(program.clone()));
+            }
+        }
+
+        self.store.insert(swap);
+        self.store.save()?;
+
+        Ok(())
+    }
+
+    /// Ticks until `on_tick` returns `false`, handing it the store and the
+    /// outcome of every tick. `on_tick` waits for the next round, by sleeping
+    /// against a node or by producing blocks on a local stand-in. Client
+    /// errors, such as a leg being unreachable, are passed on and the loop
+    /// goes on; a store error stops it, since the swaps could no longer be
+    /// persisted.
+    pub fn run(
+        &mut self,
+        mut on_tick: impl FnMut(&Store, Result<Vec<Report>>) -> bool,
+    ) -> Result<()> {
+        loop {
+            let tick = self.tick();
+
+            if let Err(Error::Store(error)) = tick {
+                return Err(Error::Store(error));
+            }
+
+            if !on_tick(&self.store, tick) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Reads new events from every leg, redeems or refunds what became
+    /// possible and persists the result.
+    pub fn tick(&mut self) -> Result<Vec<Report>> {
+        let mut reports = Vec::new();
+
+        self.observe(&mut reports)?;
+        self.settle(&mut reports)?;
+
+        self.store.save()?;
+
+        Ok(reports)
+    }
+
+    fn observe(&mut self, reports: &mut Vec<Report>) -> Result<()> {
+        for (program, leg) in &mut self.legs {
+            for event in leg.poll_events()? {
+                for swap in self.store.pending_mut() {
+                    match event {
+                        ExchangeEvent::Redeemed(exchange_id, secret)
+                            if swap.reveals(program, &exchange_id) && swap.secret.is_none() =>
+                        {
+                            swap.secret = Some(secret);
+                            reports.push(Report::SecretRevealed(exchange_id));
+                        }
+                        ExchangeEvent::Redeemed(exchange_id, _)
+                            if swap.claims(program, &exchange_id) =>
+                        {
+                            swap.status = SwapStatus::Redeemed;
+                        }
+                        ExchangeEvent::Refunded(exchange_id)
+                            if swap.reveals(program, &exchange_id) =>
+                        {
+                            swap.status = SwapStatus::Refunded;
+                        }
+                        ExchangeEvent::Refunded(exchange_id)
+                            if swap.claims(program, &exchange_id) && swap.secret.is_some() =>
+                        {
+                            swap.status = SwapStatus::Missed;
+                            reports.push(Report::Missed(exchange_id));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn settle(&mut self, reports: &mut Vec<Report>) -> Result<()> {
+        for swap in self.store.pending_mut() {
+            let (leg, call) = match swap.secret {
+                Some(secret) => (
+                    &swap.claim,
+                    Call::redeem(swap.claim.exchange_id, Secret(secret)),
+                ),
+                None => (&swap.funded, Call::refund(swap.funded.exchange_id)),
+            };
+
+            let exchange_id = leg.exchange_id;
+            let transport = self
+                .legs
+                .get_mut(&leg.program)
+                .ok_or_else(|| Error::UnknownLeg(leg.program.clone()))?;
+
+            let now = transport.block_timestamp()?;
+
+            if now < swap.retry_at || (swap.secret.is_none() && now < leg.timelock) {
+                continue;
+            }
+
+            match transport.send(self.user, &call) {
+                Ok(_) if swap.secret.is_some() => {
+                    swap.status = SwapStatus::Redeemed;
+                    reports.push(Report::Redeemed(exchange_id));
+                }
+                Ok(_) => {
+                    swap.status = SwapStatus::Refunded;
+                    reports.push(Report::Refunded(exchange_id));
+                }
+                Err(error) => {
+                    swap.attempts += 1;
+                    reports.push(Report::Failed(exchange_id, error));
+
+                    if swap.attempts >= self.retry.attempts {
+                        swap.status = SwapStatus::GaveUp;
+                        reports.push(Report::GaveUp(exchange_id));
+                    } else {
+                        swap.retry_at = now.saturating_add(self.retry.delay(swap.attempts));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+//! Runs the watchtower for Bob against an in-process `gtest` stand-in of
+//! two exchange programs, so the whole flow can be exercised offline:
+//!
+//! ```text
+//! watchtower <exchange.opt.wasm> [store]
+//! ```
+//!
+//! Alice and Bob act through their own connections, as wallets would. The
+//! watchtower only learns of their calls from the programs' events. It
+//! watches two swaps: one Alice completes and one she abandons. Every round
+//! produces a block, and the loop ends once both swaps are settled.
+
+use exchange_client::{gtest::GTestTransport, Call, Client, Secret};
+use exchange_io::ExchangeInit;
+use exchange_watchtower::{Leg, PendingSwap, Store, Watchtower};
+use gstd::ActorId;
+use gtest::System;
+use std::{env, error::Error};
+
+const ALICE: u64 = 10;
+const BOB: u64 = 11;
+const VALUE: u128 = 10_000_000_000_000;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let wasm = args
+        .next()
+        .ok_or("usage: watchtower <exchange.opt.wasm> [store]")?;
+    let store = args.next().unwrap_or_else(|| "watchtower.store".into());
+
+    let system = System::new();
+    system.init_logger();
+
+    let (alice, bob) = (actor(ALICE), actor(BOB));
+
+    for user in [ALICE, BOB] {
+        system.mint_to(user, 10 * VALUE);
+    }
+
+    let leg_a = GTestTransport::deploy(&system, &wasm, alice, ExchangeInit::default(), 0)?;
+    let leg_b = GTestTransport::deploy(&system, &wasm, bob, ExchangeInit::default(), 0)?;
+
+    let mut alice_a = Client::new(leg_a.handle());
+    let mut alice_b = Client::new(leg_b.handle());
+    let mut bob_b = Client::new(leg_b.handle());
+
+    let mut tower = Watchtower::new(bob, Store::open(store)?);
+    tower.add_leg("a", leg_a.handle());
+    tower.add_leg("b", leg_b.handle());
+
+    // Alice initiates on leg A with the longer timelock, Bob follows on leg B.
+    let now = system.block_timestamp();
+    let completed = Secret::random();
+    let abandoned = Secret::random();
+
+    for secret in [completed, abandoned] {
+        let hashlock = secret.hashlock();
+
+        let claim = Call::fund(bob, hashlock, now + 120_000).value(VALUE);
+        let funded = Call::fund(alice, hashlock, now + 60_000).value(VALUE);
+
+        let claim = Leg {
+            program: "a".into(),
+            exchange_id: alice_a.fund(alice, claim.clone())?,
+            timelock: claim.timelock(),
+        };
+        let funded = Leg {
+            program: "b".into(),
+            exchange_id: bob_b.fund(bob, funded.clone())?,
+            timelock: funded.timelock(),
+        };
+
+        tower.watch(PendingSwap::new(funded, claim))?;
+    }
+
+    let mut round = 0;
+
+    tower.run(|store, tick| {
+        match tick {
+            Ok(reports) => {
+                for report in reports {
+                    println!("round {round}: {report:?}");
+                }
+            }
+            Err(error) => eprintln!("round {round}: {error}"),
+        }
+
+        // A few blocks in, Alice takes Bob's funds, revealing the secret.
+        if round == 3 {
+            let exchange_id = exchange_io::exchange_id(bob, alice, completed.hashlock());
+
+            if let Err(error) = alice_b.redeem(alice, exchange_id, completed) {
+                eprintln!("Alice could not redeem: {error}");
+            }
+        }
+
+        round += 1;
+        GTestTransport::spend_blocks(&system, 1, &[&leg_a, &leg_b]);
+
+        store.has_pending()
+    })?;
+
+    for (index, swap) in tower.store().swaps().iter().enumerate() {
+        println!("swap {index}: {:?}", swap.status);
+    }
+
+    Ok(())
+}
+
+fn actor(id: u64) -> ActorId {
+    let mut bytes = [0u8; 32];
+    bytes[..8].copy_from_slice(&id.to_le_bytes());
+    ActorId::new(bytes)
+}
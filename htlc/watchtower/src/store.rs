@@ -0,0 +1,122 @@
+use gstd::codec::{Decode, Encode};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// One exchange of a swap, on a program registered with the watchtower.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+#[codec(crate = gstd::codec)]
+pub struct Leg {
+    pub program: String,
+    pub exchange_id: [u8; 32],
+    pub timelock: u64,
+}
+
+impl Leg {
+    fn is(&self, program: &str, exchange_id: &[u8; 32]) -> bool {
+        self.program == program && &self.exchange_id == exchange_id
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+#[codec(crate = gstd::codec)]
+pub enum SwapStatus {
+    Pending,
+    /// The claim leg was redeemed for the user.
+    Redeemed,
+    /// The funded leg expired unredeemed and was refunded to the user.
+    Refunded,
+    /// The counterparty refunded the claim leg before it could be redeemed.
+    Missed,
+    /// The redeem or refund kept failing and is left to the user.
+    GaveUp,
+}
+
+/// A swap watched on behalf of the user.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+#[codec(crate = gstd::codec)]
+pub struct PendingSwap {
+    /// Exchange the user funded. The counterparty redeeming it reveals the secret.
+    pub funded: Leg,
+    /// Exchange funded for the user under the same hashlock.
+    pub claim: Leg,
+    pub secret: Option<[u8; 32]>,
+    pub status: SwapStatus,
+    /// Failed redeem or refund calls so far.
+    pub attempts: u32,
+    /// Timestamp of the leg's chain before which the next call waits.
+    pub retry_at: u64,
+}
+
+impl PendingSwap {
+    pub fn new(funded: Leg, claim: Leg) -> Self {
+        PendingSwap {
+            funded,
+            claim,
+            secret: None,
+            status: SwapStatus::Pending,
+            attempts: 0,
+            retry_at: 0,
+        }
+    }
+
+    pub(crate) fn reveals(&self, program: &str, exchange_id: &[u8; 32]) -> bool {
+        self.funded.is(program, exchange_id)
+    }
+
+    pub(crate) fn claims(&self, program: &str, exchange_id: &[u8; 32]) -> bool {
+        self.claim.is(program, exchange_id)
+    }
+}
+
+/// Swaps persisted as SCALE in a single file, replaced atomically on save.
+#[derive(Debug)]
+pub struct Store {
+    path: PathBuf,
+    swaps: Vec<PendingSwap>,
+}
+
+impl Store {
+    /// Loads the store at `path`, starting empty if the file does not exist.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let swaps = match fs::read(&path) {
+            Ok(bytes) => Vec::<PendingSwap>::decode(&mut bytes.as_slice())
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(error),
+        };
+
+        Ok(Store { path, swaps })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+
+        fs::write(&tmp, self.swaps.encode())?;
+        fs::rename(tmp, &self.path)
+    }
+
+    pub fn insert(&mut self, swap: PendingSwap) {
+        self.swaps.push(swap);
+    }
+
+    pub fn swaps(&self) -> &[PendingSwap] {
+        &self.swaps
+    }
+
+    /// Whether some swap still needs watching.
+    pub fn has_pending(&self) -> bool {
+        self.swaps
+            .iter()
+            .any(|swap| swap.status == SwapStatus::Pending)
+    }
+
+    pub(crate) fn pending_mut(&mut self) -> impl Iterator<Item = &mut PendingSwap> {
+        self.swaps
+            .iter_mut()
+            .filter(|swap| swap.status == SwapStatus::Pending)
+    }
+}
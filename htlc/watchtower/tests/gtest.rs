@@ -0,0 +1,194 @@
+use exchange_client::{gtest::GTestTransport, Call, Client, Secret};
+use exchange_io::ExchangeInit;
+use exchange_watchtower::{Leg, PendingSwap, Report, Retry, Store, SwapStatus, Watchtower};
+use gstd::ActorId;
+use gtest::System;
+use std::{env, fs, path::PathBuf};
+
+/// Built by `cargo build --release` in the program crate.
+const WASM: &str = "../target/wasm32-unknown-unknown/release/htlc.opt.wasm";
+
+const ALICE: u64 = 10;
+const BOB: u64 = 11;
+const VALUE: u128 = 10_000_000_000_000;
+
+/// Watchtower acting for Bob over two freshly deployed legs, "a" and "b",
+/// and the connections the parties act through.
+fn watchtower<'a>(
+    system: &'a System,
+    store: &str,
+) -> (Watchtower<GTestTransport<'a>>, [GTestTransport<'a>; 2]) {
+    system.init_logger();
+
+    for user in [ALICE, BOB] {
+        system.mint_to(user, 10 * VALUE);
+    }
+
+    let leg_a = GTestTransport::deploy(system, WASM, ALICE.into(), ExchangeInit::default(), 0)
+        .expect("Failed to deploy leg a");
+    let leg_b = GTestTransport::deploy(system, WASM, BOB.into(), ExchangeInit::default(), 0)
+        .expect("Failed to deploy leg b");
+
+    let path = store_path(store);
+    let _ = fs::remove_file(&path);
+
+    let mut tower = Watchtower::new(BOB.into(), Store::open(path).unwrap());
+    tower.add_leg("a", leg_a.handle());
+    tower.add_leg("b", leg_b.handle());
+
+    (tower, [leg_a, leg_b])
+}
+
+fn store_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("watchtower-{}-{name}", std::process::id()))
+}
+
+/// Funds an exchange through `leg`, registered as `program`.
+fn fund(
+    leg: &GTestTransport<'_>,
+    program: &str,
+    from: u64,
+    to: u64,
+    hashlock: [u8; 32],
+    timelock: u64,
+) -> Leg {
+    let exchange_id = Client::new(leg.handle())
+        .fund(
+            from.into(),
+            Call::fund(to.into(), hashlock, timelock).value(VALUE),
+        )
+        .unwrap();
+
+    Leg {
+        program: program.into(),
+        exchange_id,
+        timelock,
+    }
+}
+
+#[test]
+fn refunds_expired_swap() {
+    let system = System::new();
+    let (mut tower, [leg_a, leg_b]) = watchtower(&system, "refund");
+
+    let now = system.block_timestamp();
+    let hashlock = Secret::random().hashlock();
+
+    let claim = fund(&leg_a, "a", ALICE, BOB, hashlock, now + 120_000);
+    let funded = fund(&leg_b, "b", BOB, ALICE, hashlock, now + 60_000);
+
+    tower
+        .watch(PendingSwap::new(funded.clone(), claim))
+        .unwrap();
+
+    // Nothing to do until Bob's exchange expires.
+    assert!(tower.tick().unwrap().is_empty());
+    assert_eq!(tower.store().swaps()[0].status, SwapStatus::Pending);
+
+    while system.block_timestamp() < funded.timelock {
+        GTestTransport::spend_blocks(&system, 1, &[&leg_a, &leg_b]);
+    }
+
+    let reports = tower.tick().unwrap();
+
+    assert!(matches!(
+        reports[..],
+        [Report::Refunded(exchange_id)] if exchange_id == funded.exchange_id
+    ));
+    assert_eq!(tower.store().swaps()[0].status, SwapStatus::Refunded);
+
+    // The exchange is settled: refunding again fails on the program.
+    assert!(Client::new(leg_b.handle())
+        .refund(BOB.into(), funded.exchange_id)
+        .is_err());
+
+    // The outcome survives a restart.
+    let store = Store::open(store_path("refund")).unwrap();
+    assert_eq!(store.swaps()[0].status, SwapStatus::Refunded);
+}
+
+#[test]
+fn mirrors_revealed_secret() {
+    let system = System::new();
+    let (mut tower, [leg_a, leg_b]) = watchtower(&system, "redeem");
+
+    let now = system.block_timestamp();
+    let secret = Secret::random();
+    let hashlock = secret.hashlock();
+
+    let claim = fund(&leg_a, "a", ALICE, BOB, hashlock, now + 120_000);
+    let funded = fund(&leg_b, "b", BOB, ALICE, hashlock, now + 60_000);
+
+    tower
+        .watch(PendingSwap::new(funded.clone(), claim.clone()))
+        .unwrap();
+
+    // Alice takes Bob's funds through her own connection, revealing the
+    // secret on leg b.
+    Client::new(leg_b.handle())
+        .redeem(ActorId::from(ALICE), funded.exchange_id, secret)
+        .unwrap();
+
+    let reports = tower.tick().unwrap();
+
+    assert!(matches!(
+        reports[..],
+        [Report::SecretRevealed(revealed), Report::Redeemed(redeemed)]
+            if revealed == funded.exchange_id && redeemed == claim.exchange_id
+    ));
+    assert_eq!(tower.store().swaps()[0].status, SwapStatus::Redeemed);
+    assert_eq!(tower.store().swaps()[0].secret, Some(secret.0));
+}
+
+#[test]
+fn gives_up_after_the_retries() {
+    let system = System::new();
+    let (mut tower, [leg_a, leg_b]) = watchtower(&system, "retry");
+
+    tower.set_retry(Retry {
+        attempts: 2,
+        backoff: 10_000,
+    });
+
+    let now = system.block_timestamp();
+    let secret = Secret::random();
+    let hashlock = secret.hashlock();
+
+    // The claim leg pays Alice rather than Bob, so Bob's redeem keeps failing.
+    let claim = fund(&leg_a, "a", BOB, ALICE, hashlock, now + 120_000);
+    let funded = fund(&leg_b, "b", BOB, ALICE, hashlock, now + 60_000);
+
+    tower
+        .watch(PendingSwap::new(funded.clone(), claim.clone()))
+        .unwrap();
+
+    Client::new(leg_b.handle())
+        .redeem(ActorId::from(ALICE), funded.exchange_id, secret)
+        .unwrap();
+
+    let reports = tower.tick().unwrap();
+    assert!(matches!(
+        reports[..],
+        [Report::SecretRevealed(_), Report::Failed(exchange_id, _)]
+            if exchange_id == claim.exchange_id
+    ));
+
+    // Waits out the backoff before trying again.
+    assert!(tower.tick().unwrap().is_empty());
+
+    let retry_at = tower.store().swaps()[0].retry_at;
+
+    while system.block_timestamp() < retry_at {
+        GTestTransport::spend_blocks(&system, 1, &[&leg_a, &leg_b]);
+    }
+
+    let reports = tower.tick().unwrap();
+    assert!(matches!(
+        reports[..],
+        [Report::Failed(..), Report::GaveUp(exchange_id)] if exchange_id == claim.exchange_id
+    ));
+    assert_eq!(tower.store().swaps()[0].status, SwapStatus::GaveUp);
+    assert_eq!(tower.store().swaps()[0].attempts, 2);
+
+    assert!(tower.tick().unwrap().is_empty());
+}
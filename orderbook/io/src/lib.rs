@@ -1,28 +1,172 @@
 #![no_std]
 
-use gmeta::{InOut, Metadata};
+use collections::BTreeMap;
+use core::{fmt, str::FromStr};
+use gmeta::{In, InOut, Metadata};
 use gstd::{prelude::*, ActorId};
+use sha2::Digest;
 
 pub struct OrderBookMetadata;
 
+pub type OrderId = [u8; 32];
+
+/// Order time-to-live when `AddOrder` does not set one: 24 hours.
+pub const DEFAULT_ORDER_TTL: u64 = 86_400_000;
+pub const MIN_ORDER_TTL: u64 = 60_000;
+pub const MAX_ORDER_TTL: u64 = 30 * DEFAULT_ORDER_TTL;
+
+/// Number of fractional digits a [`Decimal`] keeps.
+pub const DECIMALS: u32 = 12;
+
+const SCALE: u128 = 10u128.pow(DECIMALS);
+
+/// Non-negative fixed-point number, stored as an integer count of
+/// `10^-DECIMALS` units. Used for amounts, prices and slippage.
+///
+/// Parses from and prints as a decimal string, so `"1"`, `"1.0"` and
+/// `"1.000"` are the same value. Arithmetic is checked and returns `None`
+/// on overflow.
+#[derive(
+    Debug, Clone, Copy, Encode, Decode, TypeInfo, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct Decimal(pub u128);
+
+impl Decimal {
+    pub const ZERO: Decimal = Decimal(0);
+    pub const ONE: Decimal = Decimal(SCALE);
+    pub const HUNDRED: Decimal = Decimal(100 * SCALE);
+
+    pub fn from_integer(value: u128) -> Option<Decimal> {
+        value.checked_mul(SCALE).map(Decimal)
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: Decimal) -> Option<Decimal> {
+        self.0.checked_add(other.0).map(Decimal)
+    }
+
+    pub fn checked_sub(self, other: Decimal) -> Option<Decimal> {
+        self.0.checked_sub(other.0).map(Decimal)
+    }
+
+    /// Product, rounded down.
+    pub fn checked_mul(self, other: Decimal) -> Option<Decimal> {
+        // Split `self` so only the fractional part gets multiplied at full
+        // scale, which keeps products of large values in range.
+        let whole = (self.0 / SCALE).checked_mul(other.0)?;
+        let fraction = (self.0 % SCALE).checked_mul(other.0)? / SCALE;
+
+        whole.checked_add(fraction).map(Decimal)
+    }
+
+    /// Quotient, rounded down. `None` when dividing by zero.
+    pub fn checked_div(self, other: Decimal) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+
+        let whole = (self.0 / other.0).checked_mul(SCALE)?;
+        let fraction = (self.0 % other.0).checked_mul(SCALE)? / other.0;
+
+        whole.checked_add(fraction).map(Decimal)
+    }
+
+    /// Whether the value has at most `decimals` fractional digits.
+    pub fn fits_decimals(self, decimals: u8) -> bool {
+        match DECIMALS.checked_sub(decimals.into()) {
+            Some(dropped) => self.is_multiple_of(Decimal(10u128.pow(dropped))),
+            None => true,
+        }
+    }
+
+    /// Whether the value is a whole number of `step`s. Any value is a
+    /// multiple of a zero step.
+    pub fn is_multiple_of(self, step: Decimal) -> bool {
+        matches!(self.0.checked_rem(step.0), None | Some(0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum DecimalError {
+    Empty,
+    InvalidDigit,
+    /// More than [`DECIMALS`] fractional digits.
+    TooPrecise,
+    Overflow,
+}
+
+impl FromStr for Decimal {
+    type Err = DecimalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(DecimalError::Empty);
+        }
+
+        if fraction.len() > DECIMALS as usize {
+            return Err(DecimalError::TooPrecise);
+        }
+
+        let mut units: u128 = 0;
+
+        for digit in whole.bytes().chain(fraction.bytes()) {
+            if !digit.is_ascii_digit() {
+                return Err(DecimalError::InvalidDigit);
+            }
+
+            units = units
+                .checked_mul(10)
+                .and_then(|units| units.checked_add((digit - b'0') as u128))
+                .ok_or(DecimalError::Overflow)?;
+        }
+
+        units
+            .checked_mul(10u128.pow(DECIMALS - fraction.len() as u32))
+            .map(Decimal)
+            .ok_or(DecimalError::Overflow)
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (whole, fraction) = (self.0 / SCALE, self.0 % SCALE);
+
+        if fraction == 0 {
+            return write!(f, "{whole}");
+        }
+
+        let digits = format!("{fraction:0width$}", width = DECIMALS as usize);
+
+        write!(f, "{whole}.{}", digits.trim_end_matches('0'))
+    }
+}
+
 #[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
 #[codec(crate = gstd::codec)]
 #[scale_info(crate = gstd::scale_info)]
-pub enum NetworkIdentityType
-{
+pub enum NetworkIdentityType {
     EMPTY,
     SOCKS5,
-    #[default] ORDER_SERVICE,
+    #[default]
+    ORDER_SERVICE,
     IPV4,
     IPV6,
-    Vara
+    Vara,
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
 #[codec(crate = gstd::codec)]
 #[scale_info(crate = gstd::scale_info)]
-pub struct NetworkIdentity
-{
+pub struct NetworkIdentity {
     pub network_type: NetworkIdentityType,
     pub identity: String,
 }
@@ -30,8 +174,7 @@ pub struct NetworkIdentity
 #[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
 #[codec(crate = gstd::codec)]
 #[scale_info(crate = gstd::scale_info)]
-pub struct Participant
-{
+pub struct Participant {
     pub wallet_uuid: String,
     pub network_identity: NetworkIdentity,
 }
@@ -51,85 +194,678 @@ pub struct Ledger {
 pub struct Asset {
     pub ledger: Ledger,
     pub name: String,
-    pub nominal_amount: String,
+    pub nominal_amount: Decimal,
+}
+
+impl Asset {
+    pub fn key(&self) -> AssetKey {
+        AssetKey {
+            ledger: self.ledger.name.clone(),
+            network: self.ledger.network.clone(),
+            chain_id: self.ledger.chain_id,
+            symbol: self.name.clone(),
+        }
+    }
+}
+
+/// What tells assets apart: the same symbol on another ledger, network or
+/// chain is another asset.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct AssetKey {
+    pub ledger: String,
+    pub network: String,
+    pub chain_id: i64,
+    pub symbol: String,
+}
+
+/// Registered asset.
+#[derive(Debug, Clone, Copy, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct AssetInfo {
+    /// Fractional digits amounts of the asset may have, at most
+    /// [`DECIMALS`].
+    pub decimals: u8,
+    /// Smallest amount of the asset an order may trade.
+    pub min_size: Decimal,
+}
+
+/// Listed pair.
+#[derive(Debug, Clone, Copy, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct PairInfo {
+    /// Step order prices on the pair move in, in quote units per base unit.
+    /// Zero allows any price.
+    pub tick_size: Decimal,
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
 #[codec(crate = gstd::codec)]
 #[scale_info(crate = gstd::scale_info)]
 pub struct Order {
-    pub id: String,
+    pub id: OrderId,
     pub user: ActorId,
-    pub user_slippage: String,
+    /// Accepted price deviation, in percent. Only market orders trade
+    /// beyond their price.
+    pub user_slippage: Decimal,
     pub alpha_asset: Asset,
     pub beta_asset: Asset,
-    pub alpha_asset_price: String,
-    pub beta_asset_price: String,
+    pub alpha_asset_price: Decimal,
+    pub beta_asset_price: Decimal,
     pub is_locked: bool,
     pub is_inactive: bool,
     pub inactive_time_start: u64,
     pub valid_until: u64,
     pub creator: Participant,
+    /// Base asset quantity the order trades, set from its nominal amounts
+    /// when it is added.
+    pub quantity: Decimal,
+    /// Part of `quantity` not filled yet. A partially filled order keeps
+    /// resting for the rest; a filled one is locked.
+    pub remaining: Decimal,
+    pub order_type: OrderType,
+    pub time_in_force: TimeInForce,
+    /// Makes a limit order a stop-limit order, and a market order a stop
+    /// order. It stays inactive, and out of the book, until the oracle price
+    /// of the trigger's asset meets the trigger.
+    pub trigger: Option<Trigger>,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct Trigger {
+    /// Asset whose oracle price the trigger watches, one of the order's two
+    /// assets.
+    pub asset: AssetKey,
+    pub price: Decimal,
+    pub condition: TriggerCondition,
+}
+
+#[derive(Debug, Clone, Copy, Encode, Decode, TypeInfo, PartialEq, Eq, PartialOrd, Ord)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum TriggerCondition {
+    AtOrAbove,
+    AtOrBelow,
+}
+
+impl Trigger {
+    pub fn is_met(&self, price: Decimal) -> bool {
+        match self.condition {
+            TriggerCondition::AtOrAbove => price >= self.price,
+            TriggerCondition::AtOrBelow => price <= self.price,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum OrderType {
+    /// Trades at its price or better.
+    #[default]
+    Limit,
+    /// Trades down the book as far as its slippage allows from the price
+    /// its amounts imply, so a bid never pays much more than it committed.
+    /// Never rests, so it must be immediate-or-cancel or fill-or-kill.
+    Market,
+    /// A limit order that only ever rests. Rejected if it would trade on
+    /// arrival, and never takes when orders are checked.
+    PostOnly,
+}
+
+#[derive(Debug, Clone, Copy, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum TimeInForce {
+    /// Rests until filled, deleted or expired.
+    #[default]
+    GoodTillCancelled,
+    /// Trades what it can on arrival and drops the rest.
+    ImmediateOrCancel,
+    /// Trades its whole quantity on arrival or nothing at all.
+    FillOrKill,
+}
+
+impl Order {
+    /// Pair the order trades on.
+    pub fn pair(&self) -> Pair {
+        Pair::new(self.alpha_asset.key(), self.beta_asset.key())
+    }
+
+    /// An order selling the pair's base asset asks, one buying it bids.
+    pub fn side(&self) -> Side {
+        if self.alpha_asset.key() <= self.beta_asset.key() {
+            Side::Ask
+        } else {
+            Side::Bid
+        }
+    }
+
+    /// Limit price in quote units per base unit, implied by the nominal
+    /// amounts. `None` if it overflows.
+    pub fn price(&self) -> Option<Decimal> {
+        let (alpha, beta) = (
+            self.alpha_asset.nominal_amount,
+            self.beta_asset.nominal_amount,
+        );
+
+        match self.side() {
+            Side::Ask => beta.checked_div(alpha),
+            Side::Bid => alpha.checked_div(beta),
+        }
+    }
+
+    /// Base asset amount of the order: what it sells when asking, what it
+    /// buys when bidding.
+    pub fn base_quantity(&self) -> Decimal {
+        match self.side() {
+            Side::Ask => self.alpha_asset.nominal_amount,
+            Side::Bid => self.beta_asset.nominal_amount,
+        }
+    }
+
+    /// Worst price the order accepts once its slippage is applied.
+    pub fn price_limit(&self) -> Option<Decimal> {
+        let price = self.price()?;
+        let deviation = price
+            .checked_mul(self.user_slippage)?
+            .checked_div(Decimal::HUNDRED)?;
+
+        match self.side() {
+            Side::Ask => price.checked_sub(deviation),
+            Side::Bid => price.checked_add(deviation),
+        }
+    }
+}
+
+/// Market between two assets. `base` sorts before `quote`, so both
+/// directions of a trade land on the same pair.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct Pair {
+    pub base: AssetKey,
+    pub quote: AssetKey,
+}
+
+impl Pair {
+    pub fn new(a: AssetKey, b: AssetKey) -> Self {
+        let (base, quote) = if a <= b { (a, b) } else { (b, a) };
+
+        Pair { base, quote }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Encode, Decode, TypeInfo, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum Side {
+    /// Buys the base asset.
+    Bid,
+    /// Sells the base asset.
+    Ask,
+}
+
+impl Side {
+    pub fn opposite(self) -> Side {
+        match self {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
 #[codec(crate = gstd::codec)]
 #[scale_info(crate = gstd::scale_info)]
-pub enum SwapRole
-{
-    #[default] EMPTY,
+pub enum SwapRole {
+    #[default]
+    EMPTY,
     Alice,
-    Bob
+    Bob,
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
 #[codec(crate = gstd::codec)]
 #[scale_info(crate = gstd::scale_info)]
-pub enum MatchType
-{
-    #[default] None,
+pub enum MatchType {
+    #[default]
+    None,
     Exact,
     Partial,
     WithSlippage,
-    OneToMany
+    OneToMany,
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
 #[codec(crate = gstd::codec)]
 #[scale_info(crate = gstd::scale_info)]
-pub struct OrderPair
-{
+pub struct OrderPair {
     pub n1: Order,
     pub n2: Order,
     pub role: SwapRole,
-    pub match_type: MatchType
+    pub match_type: MatchType,
+    /// What was traded, with `n1` as the maker and `n2` as the taker.
+    pub fill: Fill,
+}
+
+/// One trade between a resting order and an incoming one, at the resting
+/// order's price.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct Fill {
+    pub maker: OrderId,
+    pub taker: OrderId,
+    pub price: Decimal,
+    pub base_amount: Decimal,
+    pub quote_amount: Decimal,
+    pub timestamp: u64,
+}
+
+impl Fill {
+    /// Amount of its alpha asset an order on `side` delivers in this fill.
+    pub fn amount(&self, side: Side) -> Decimal {
+        match side {
+            Side::Ask => self.base_amount,
+            Side::Bid => self.quote_amount,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
 #[codec(crate = gstd::codec)]
 #[scale_info(crate = gstd::scale_info)]
 pub enum Action {
-    AddOrder(Order),
-    DeleteOrder(String),
-    ModifyOrder (Order),
-    CheckOrders(bool)
+    /// Adds an order living for the given milliseconds, between
+    /// [`MIN_ORDER_TTL`] and [`MAX_ORDER_TTL`], or [`DEFAULT_ORDER_TTL`]
+    /// if `None`. The order trades against the book on arrival and only
+    /// what is left rests.
+    AddOrder(Order, Option<u64>),
+    /// Owner only, and only while the order is not locked.
+    DeleteOrder(OrderId),
+    /// Owner only, and only while the order is not locked. Moves the order
+    /// to the back of its new price level, trading first whatever it now
+    /// crosses. Rejected if that would make a post-only order trade.
+    ModifyOrder(OrderId, OrderUpdate),
+    /// Trades the caller's orders against the book again, for ones that
+    /// became matchable after they were added. The flag is unused.
+    CheckOrders(bool),
+    /// Removes expired orders. Only the program sends it, delayed to the
+    /// next expiry.
+    SweepExpired,
+    /// Fetches the oracle price of an asset, by its symbol, and triggers the
+    /// stop orders on that asset it meets. Anyone may call it.
+    Poke(AssetKey),
+    /// Triggers the stop orders an asset price meets, for oracles that push
+    /// their prices. Only the oracle may send it; the price oracle in this
+    /// repository does not, so its prices reach stop orders through `Poke`.
+    PriceUpdate(AssetKey, Decimal),
+    /// Owner only.
+    SetOracle(ActorId),
+    /// Owner only. Adds an asset or replaces its info.
+    RegisterAsset(AssetKey, AssetInfo),
+    /// Owner only. Lists the pair of two registered assets or replaces its
+    /// info.
+    ListPair(AssetKey, AssetKey, PairInfo),
+    /// Owner only. Stops new orders and modifications on a pair. Orders
+    /// already on it stay and keep trading.
+    DelistPair(Pair),
 }
 
-#[derive(Debug,  Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
 #[codec(crate = gstd::codec)]
 #[scale_info(crate = gstd::scale_info)]
 pub enum Event {
     OrderAdded(Order),
-    OrderDeleted(String),
+    OrderDeleted(OrderId),
     OrderModified(Order),
     OrderMatched(OrderPair),
+    /// Sent to the owner of an order the sweeper removed.
+    OrderExpired(OrderId),
+    /// Sent to the owner of a stop order when its trigger is met.
+    OrderTriggered(OrderId),
+    /// Sent to the owner of a triggered immediate order that found nothing
+    /// to trade with.
+    OrderCancelled(OrderId),
+    /// Reply to `Poke` and `PriceUpdate`, with the orders they triggered.
+    StopsTriggered(Vec<OrderId>),
+    OracleSet(ActorId),
+    AssetRegistered(AssetKey),
+    PairListed(Pair),
+    PairDelisted(Pair),
+}
+
+/// Fields `ModifyOrder` may change. `None` keeps the current value. The
+/// order price follows from the amounts; the asset prices are not matched
+/// on and stay as submitted.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct OrderUpdate {
+    pub user_slippage: Option<Decimal>,
+    pub alpha_amount: Option<Decimal>,
+    pub beta_amount: Option<Decimal>,
+    pub creator: Option<Participant>,
+}
+
+impl OrderUpdate {
+    pub fn apply(&self, order: &mut Order) {
+        if let Some(user_slippage) = self.user_slippage {
+            order.user_slippage = user_slippage;
+        }
+        if let Some(alpha_amount) = self.alpha_amount {
+            order.alpha_asset.nominal_amount = alpha_amount;
+        }
+        if let Some(beta_amount) = self.beta_amount {
+            order.beta_asset.nominal_amount = beta_amount;
+        }
+        if let Some(creator) = &self.creator {
+            order.creator = creator.clone();
+        }
+    }
+}
+
+/// A change its owner made to an order.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct Amendment {
+    pub timestamp: u64,
+    pub change: Change,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum Change {
+    Modified(OrderUpdate),
+    Deleted,
+}
+
+#[derive(Debug, Clone, Copy, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum OrderError {
+    NotFound,
+    /// The caller does not own the order.
+    Unauthorized,
+    /// The order is matched and can no longer change.
+    Locked,
+    /// A post-only order would have traded on arrival.
+    WouldCross,
+    /// An immediate order found nothing to trade with, or a fill-or-kill
+    /// one not enough.
+    Unfilled,
+    /// The caller is not the program owner or the oracle.
+    NotAllowed,
+    NoOracle,
+    /// The oracle has no usable price for the asset.
+    NoPrice,
+    /// An asset is not registered.
+    UnknownAsset,
+    /// The order's pair is not listed.
+    UnknownPair,
+    /// An amount has more fractional digits than its asset.
+    TooPrecise,
+    /// An amount is below its asset's min size.
+    TooSmall,
+    /// The order price is not a multiple of the pair's tick size.
+    OffTick,
+    /// A modified quantity does not exceed what the order already filled.
+    BelowFilled,
+    /// The order trades an asset for itself, has a zero amount or price,
+    /// slippage above 100 percent, an order type that its time in force or
+    /// trigger rule out, or a trigger on neither of its assets.
+    InvalidOrder,
+    /// The time-to-live is outside [`MIN_ORDER_TTL`]..=[`MAX_ORDER_TTL`].
+    TtlOutOfBounds,
+    /// An asset would have more decimals than [`DECIMALS`].
+    TooManyDecimals,
+    /// A pair would be of an asset with itself.
+    SameAsset,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum StateQuery {
+    /// Orders of everyone but the caller.
+    Orders,
+    /// Fills of an order, oldest first.
+    Fills(OrderId),
+    /// Amendments of an order, oldest first.
+    Amendments(OrderId),
+    /// Nonce the next added order gets its id from.
+    Nonce,
+    /// Registered assets.
+    Assets,
+    /// Listed pairs.
+    Pairs,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum StateReply {
+    Orders(Vec<Order>),
+    Fills(Vec<Fill>),
+    Amendments(Vec<Amendment>),
+    Nonce(u64),
+    Assets(Vec<(AssetKey, AssetInfo)>),
+    Pairs(Vec<(Pair, PairInfo)>),
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct OrderBookInit {
+    /// `price_oracle` program that prices stop order triggers.
+    pub oracle: Option<ActorId>,
+}
+
+/// Message of the `price_oracle` program. It builds against another gstd,
+/// so its types are mirrored here.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum OracleAction {
+    SetPrice {
+        name: String,
+        price: String,
+        interval: String,
+    },
+    GetPrices {
+        crypto: String,
+    },
+}
+
+/// Reply of the `price_oracle` program.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum OracleEvent {
+    PricesSet,
+    Prices(Option<OraclePrices>),
+    SpecificPrice(Option<OraclePrices>),
+    AllPrices(BTreeMap<String, OraclePrices>),
+    NewManager(ActorId),
+    NewValue { value: u128 },
+    Unauthorized,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct OraclePrices {
+    pub minute: String,
+    pub hourly: String,
+    pub weekly: String,
 }
 
 impl Metadata for OrderBookMetadata {
-    type Init = ();
-    type Handle = InOut<Action, Event>;
+    type Init = In<OrderBookInit>;
+    type Handle = InOut<Action, Result<Event, OrderError>>;
     type Others = ();
     type Reply = ();
     type Signal = ();
-    type State = InOut<(), Vec<Order>>;
-}
\ No newline at end of file
+    type State = InOut<StateQuery, StateReply>;
+}
+
+/// Id of the order added with program-wide `nonce` by `user`. Every added
+/// order takes the next nonce, so ids never repeat, even among orders of one
+/// user in one block. Which nonce an order gets is only known once it is
+/// handled, so the id comes with the `OrderAdded` reply.
+pub fn order_id(nonce: u64, user: ActorId) -> OrderId {
+    let mut output = [0u8; 32];
+    output.copy_from_slice(sha2::Sha256::digest((nonce, user).encode()).as_slice());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_whole_and_fractional_parts() {
+        assert_eq!(decimal("0"), Decimal::ZERO);
+        assert_eq!(decimal("0.000"), Decimal::ZERO);
+        assert_eq!(decimal("1"), Decimal::ONE);
+        assert_eq!(decimal("1.000"), Decimal::ONE);
+        assert_eq!(decimal("007"), Decimal::from_integer(7).unwrap());
+        assert_eq!(decimal("100"), Decimal::HUNDRED);
+        assert_eq!(decimal("1.5"), Decimal(1_500_000_000_000));
+        assert_eq!(decimal("0.000000000001"), Decimal(1));
+
+        // Either side of the dot may be left out, not both.
+        assert_eq!(decimal(".5"), decimal("0.5"));
+        assert_eq!(decimal("5."), decimal("5"));
+        assert_eq!(".".parse::<Decimal>(), Err(DecimalError::Empty));
+        assert_eq!("".parse::<Decimal>(), Err(DecimalError::Empty));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for s in ["-1", "+1", " 1", "1 ", "1e3", "1,5", "1.2.3", "..5", "0x10"] {
+            assert_eq!(s.parse::<Decimal>(), Err(DecimalError::InvalidDigit), "{s}");
+        }
+    }
+
+    #[test]
+    fn keeps_at_most_twelve_fractional_digits() {
+        assert_eq!(decimal("1.000000000001"), Decimal(SCALE + 1));
+        assert_eq!(
+            "1.0000000000001".parse::<Decimal>(),
+            Err(DecimalError::TooPrecise)
+        );
+        // Trailing zeros count too.
+        assert_eq!(
+            "1.0000000000000".parse::<Decimal>(),
+            Err(DecimalError::TooPrecise)
+        );
+    }
+
+    #[test]
+    fn rejects_values_out_of_range() {
+        let max = "340282366920938463463374607.431768211455";
+
+        assert_eq!(decimal(max), Decimal(u128::MAX));
+        assert_eq!(
+            "340282366920938463463374607.431768211456".parse::<Decimal>(),
+            Err(DecimalError::Overflow)
+        );
+        // In range as an integer count of units, not once scaled.
+        assert_eq!(
+            "340282366920938463463374608".parse::<Decimal>(),
+            Err(DecimalError::Overflow)
+        );
+        assert_eq!(
+            "1000000000000000000000000000000000000000".parse::<Decimal>(),
+            Err(DecimalError::Overflow)
+        );
+        assert_eq!(Decimal::from_integer(u128::MAX), None);
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for s in [
+            "0",
+            "1",
+            "0.5",
+            "12.3",
+            "1.000000000001",
+            "0.000000000001",
+            "340282366920938463463374607.431768211455",
+        ] {
+            assert_eq!(decimal(s).to_string(), s);
+        }
+
+        // Printing drops redundant zeros.
+        assert_eq!(decimal("1.50").to_string(), "1.5");
+        assert_eq!(decimal("007.0").to_string(), "7");
+        assert_eq!(decimal(".25").to_string(), "0.25");
+    }
+
+    #[test]
+    fn multiplication_rounds_down() {
+        assert_eq!(
+            decimal("1.5").checked_mul(decimal("1.5")),
+            Some(decimal("2.25"))
+        );
+        assert_eq!(
+            decimal("0.000000000001").checked_mul(decimal("0.5")),
+            Some(Decimal::ZERO)
+        );
+        assert_eq!(
+            decimal("0.000000000003").checked_mul(decimal("0.5")),
+            Some(decimal("0.000000000001"))
+        );
+        assert_eq!(
+            decimal("123.456").checked_mul(Decimal::ZERO),
+            Some(Decimal::ZERO)
+        );
+
+        // Large values stay in range as long as the product does.
+        let large = Decimal::from_integer(10u128.pow(13)).unwrap();
+        assert_eq!(
+            large.checked_mul(large),
+            Decimal::from_integer(10u128.pow(26))
+        );
+        assert_eq!(
+            Decimal(u128::MAX).checked_mul(Decimal::ONE),
+            Some(Decimal(u128::MAX))
+        );
+        assert_eq!(Decimal(u128::MAX).checked_mul(decimal("2")), None);
+    }
+
+    #[test]
+    fn division_rounds_down() {
+        assert_eq!(
+            Decimal::ONE.checked_div(decimal("3")),
+            Some(decimal("0.333333333333"))
+        );
+        assert_eq!(
+            decimal("2").checked_div(decimal("3")),
+            Some(decimal("0.666666666666"))
+        );
+        assert_eq!(
+            decimal("0.000000000001").checked_div(decimal("2")),
+            Some(Decimal::ZERO)
+        );
+        assert_eq!(
+            decimal("7.5").checked_div(decimal("2.5")),
+            Some(decimal("3"))
+        );
+        assert_eq!(Decimal::ONE.checked_div(Decimal::ZERO), None);
+        assert_eq!(Decimal(u128::MAX).checked_div(decimal("0.5")), None);
+    }
+}
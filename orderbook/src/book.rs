@@ -0,0 +1,101 @@
+use collections::BTreeMap;
+use gstd::prelude::*;
+use order_book_io::{Decimal, OrderId, Side, Trigger, TriggerCondition};
+
+/// Order ids at one price, by arrival.
+type Level = BTreeMap<u64, OrderId>;
+
+/// Resting orders of one pair, kept in price-time priority.
+#[derive(Debug, Clone, Default)]
+pub struct Book {
+    bids: BTreeMap<Decimal, Level>,
+    asks: BTreeMap<Decimal, Level>,
+}
+
+impl Book {
+    pub fn insert(&mut self, side: Side, price: Decimal, arrival: u64, id: OrderId) {
+        self.levels_mut(side)
+            .entry(price)
+            .or_default()
+            .insert(arrival, id);
+    }
+
+    pub fn remove(&mut self, side: Side, price: Decimal, arrival: u64) {
+        let levels = self.levels_mut(side);
+
+        if let Some(level) = levels.get_mut(&price) {
+            level.remove(&arrival);
+
+            if level.is_empty() {
+                levels.remove(&price);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+
+    /// Orders on `side` with their price, best price first and oldest first
+    /// within a price.
+    pub fn iter(&self, side: Side) -> Box<dyn Iterator<Item = (Decimal, &OrderId)> + '_> {
+        match side {
+            Side::Bid => Box::new(self.bids.iter().rev().flat_map(level_orders)),
+            Side::Ask => Box::new(self.asks.iter().flat_map(level_orders)),
+        }
+    }
+
+    fn levels_mut(&mut self, side: Side) -> &mut BTreeMap<Decimal, Level> {
+        match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        }
+    }
+}
+
+fn level_orders<'a>(
+    (price, level): (&'a Decimal, &'a Level),
+) -> impl Iterator<Item = (Decimal, &'a OrderId)> {
+    level.values().map(move |id| (*price, id))
+}
+
+/// Inactive stop orders on one asset, by trigger price and arrival.
+#[derive(Debug, Clone, Default)]
+pub struct Stops {
+    at_or_above: BTreeMap<(Decimal, u64), OrderId>,
+    at_or_below: BTreeMap<(Decimal, u64), OrderId>,
+}
+
+impl Stops {
+    pub fn insert(&mut self, trigger: &Trigger, arrival: u64, id: OrderId) {
+        self.triggers_mut(trigger.condition)
+            .insert((trigger.price, arrival), id);
+    }
+
+    pub fn remove(&mut self, trigger: &Trigger, arrival: u64) {
+        self.triggers_mut(trigger.condition)
+            .remove(&(trigger.price, arrival));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.at_or_above.is_empty() && self.at_or_below.is_empty()
+    }
+
+    /// Orders whose trigger `price` meets.
+    pub fn triggered(&self, price: Decimal) -> impl Iterator<Item = OrderId> + '_ {
+        self.at_or_above
+            .range(..=(price, u64::MAX))
+            .chain(self.at_or_below.range((price, 0)..))
+            .map(|(_, id)| *id)
+    }
+
+    fn triggers_mut(
+        &mut self,
+        condition: TriggerCondition,
+    ) -> &mut BTreeMap<(Decimal, u64), OrderId> {
+        match condition {
+            TriggerCondition::AtOrAbove => &mut self.at_or_above,
+            TriggerCondition::AtOrBelow => &mut self.at_or_below,
+        }
+    }
+}
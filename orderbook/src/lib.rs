@@ -2,198 +2,864 @@
 
 extern crate alloc;
 
+mod book;
+
 use alloc::vec::Vec;
-use gstd::{msg, prelude::*};
-use fixed::types::I16F16;
-use gstd::ptr::addr_of_mut;
-use order_book_io::{*};
-use sha2::Digest;
-use gstd::fmt::{*};
+use book::{Book, Stops};
+use collections::BTreeMap;
+use gstd::{exec, msg, prelude::*, ActorId};
+use order_book_io::*;
 
-static mut ORDER_BOOK: Vec<Order> = Vec::new();
+/// Expected block time, used to turn expiry times into block delays.
+const BLOCK_TIME: u64 = 3_000;
+/// Gas for a sweep message.
+const SWEEP_GAS: u64 = 10_000_000_000;
+/// Most orders one sweep removes, to stay within its gas. The rest go in
+/// the next block.
+const MAX_SWEEP: usize = 50;
+/// Most stop orders one price triggers, to stay within gas. The rest
+/// trigger on the next price.
+const MAX_TRIGGER: usize = 20;
 
-#[no_mangle]
-extern "C" fn handle() {
-    let action: Action = msg::load().expect("Failed to load Action message");
-    
-    let subject: [u8; 32] = array::from_fn(|i| i as u8 + 1);
-    let (seed, _block_number) = gstd::exec::random(subject).expect("Error in random");
+#[derive(Debug, Default)]
+struct OrderBook {
+    owner: ActorId,
+    oracle: Option<ActorId>,
+    orders: BTreeMap<OrderId, Order>,
+    /// Arrival number of every order, its priority within a price level.
+    arrivals: BTreeMap<OrderId, u64>,
+    books: BTreeMap<Pair, Book>,
+    next_arrival: u64,
+    fills: Vec<Fill>,
+    /// Positions in `fills` of every order's fills.
+    order_fills: BTreeMap<OrderId, Vec<usize>>,
+    /// Unlocked orders by `valid_until`, then arrival.
+    expiries: BTreeMap<(u64, u64), OrderId>,
+    /// Expiry time the pending sweep was scheduled for.
+    next_sweep: Option<u64>,
+    amendments: BTreeMap<OrderId, Vec<Amendment>>,
+    /// Number of orders added so far.
+    nonce: u64,
+    /// Inactive stop orders by their trigger asset.
+    stops: BTreeMap<AssetKey, Stops>,
+    assets: BTreeMap<AssetKey, AssetInfo>,
+    pairs: BTreeMap<Pair, PairInfo>,
+}
 
-    match action {
-        Action::AddOrder(mut order) => {
+impl OrderBook {
+    /// Stores `order`. Unless it is locked, inactive or filled it also rests
+    /// in the book of its pair, behind the orders already at its price.
+    fn insert(&mut self, order: Order) {
+        let arrival = self.next_arrival;
+        self.next_arrival += 1;
+
+        self.arrivals.insert(order.id, arrival);
+
+        if !order.is_locked {
+            self.expiries.insert((order.valid_until, arrival), order.id);
+        }
+
+        if let (true, Some(trigger)) = (order.is_inactive, &order.trigger) {
+            self.stops
+                .entry(trigger.asset.clone())
+                .or_default()
+                .insert(trigger, arrival, order.id);
+        }
+
+        if !order.is_locked && !order.is_inactive && !order.remaining.is_zero() {
+            let price = order.price().expect("Order price out of range");
+
+            self.books.entry(order.pair()).or_default().insert(
+                order.side(),
+                price,
+                arrival,
+                order.id,
+            );
+        }
+
+        self.orders.insert(order.id, order);
+    }
+
+    fn remove(&mut self, id: OrderId) -> Option<Order> {
+        let order = self.orders.remove(&id)?;
+
+        self.unrest(&order);
+
+        if let Some(arrival) = self.arrivals.remove(&id) {
+            self.expiries.remove(&(order.valid_until, arrival));
+
+            if let (true, Some(trigger)) = (order.is_inactive, &order.trigger) {
+                let asset = &trigger.asset;
+
+                if let Some(stops) = self.stops.get_mut(asset) {
+                    stops.remove(trigger, arrival);
+
+                    if stops.is_empty() {
+                        self.stops.remove(asset);
+                    }
+                }
+            }
+        }
+
+        Some(order)
+    }
+
+    /// Turns a triggered stop order into the limit or market order it
+    /// stands for, arriving now.
+    fn activate(&mut self, id: OrderId) {
+        let Some(mut order) = self.remove(id) else {
+            return;
+        };
 
-        order.user = msg::source();
-        order.id = encode_hex(&sha2_256(&seed)); 
-        order.valid_until = gstd::exec::block_timestamp() + 86400000; //cuurent blocktime + 24 hours
-        order.is_locked = false;
         order.is_inactive = false;
         order.inactive_time_start = 0;
+        order.trigger = None;
+
+        self.insert(order);
+    }
 
-        unsafe {
-            ORDER_BOOK.push(order.clone());
+    /// Order `id` if `user` owns it and it can still change.
+    fn owned(&self, id: OrderId, user: ActorId) -> Result<&Order, OrderError> {
+        let order = self.orders.get(&id).ok_or(OrderError::NotFound)?;
+
+        if order.user != user {
+            return Err(OrderError::Unauthorized);
         }
 
-        msg::reply(Event::OrderAdded(order.clone()), 0).expect("Failed add order");
-        
-        },
-        Action::DeleteOrder(id) => {
-            unsafe {
-                ORDER_BOOK.retain(|order| order.id != id)
+        if order.is_locked {
+            return Err(OrderError::Locked);
+        }
+
+        Ok(order)
+    }
+
+    fn delete(&mut self, id: OrderId, user: ActorId, now: u64) -> Result<(), OrderError> {
+        self.owned(id, user)?;
+        self.remove(id);
+        self.amend(id, Change::Deleted, now);
+
+        Ok(())
+    }
+
+    fn modify(
+        &mut self,
+        id: OrderId,
+        user: ActorId,
+        update: OrderUpdate,
+        now: u64,
+    ) -> Result<Order, OrderError> {
+        let order = self.owned(id, user)?.clone();
+
+        let mut updated_order = order.clone();
+        update.apply(&mut updated_order);
+
+        validate_order(&updated_order)?;
+        self.check_listing(&updated_order)?;
+
+        // What already traded stays traded.
+        let filled = order
+            .quantity
+            .checked_sub(order.remaining)
+            .unwrap_or_default();
+
+        updated_order.quantity = updated_order.base_quantity();
+        updated_order.remaining = updated_order
+            .quantity
+            .checked_sub(filled)
+            .filter(|remaining| !remaining.is_zero())
+            .ok_or(OrderError::BelowFilled)?;
+
+        if updated_order.order_type == OrderType::PostOnly
+            && self.find_match(&updated_order, now).is_some()
+        {
+            return Err(OrderError::WouldCross);
+        }
+
+        self.remove(id);
+        self.insert(updated_order.clone());
+        self.amend(id, Change::Modified(update), now);
+
+        Ok(updated_order)
+    }
+
+    /// Checks `order` against the registry: its assets registered, its pair
+    /// listed, its amounts within their assets' decimals and min sizes and,
+    /// unless it is a market order, its price on the pair's tick.
+    fn check_listing(&self, order: &Order) -> Result<(), OrderError> {
+        for asset in [&order.alpha_asset, &order.beta_asset] {
+            let info = self
+                .assets
+                .get(&asset.key())
+                .ok_or(OrderError::UnknownAsset)?;
+
+            if !asset.nominal_amount.fits_decimals(info.decimals) {
+                return Err(OrderError::TooPrecise);
             }
 
-            msg::reply(Event::OrderDeleted(id), 0).expect("Failed to reply with OrderDeleted event");
-        },
-        Action::ModifyOrder (updated_order ) => {
-            unsafe {
-                if let Some(order) = ORDER_BOOK.iter_mut().find(|order| order.id == updated_order.id) {
-                    *order = updated_order;
+            if asset.nominal_amount < info.min_size {
+                return Err(OrderError::TooSmall);
+            }
+        }
+
+        let pair = self
+            .pairs
+            .get(&order.pair())
+            .ok_or(OrderError::UnknownPair)?;
+        let price = order.price().expect("Order price out of range");
+
+        if order.order_type != OrderType::Market && !price.is_multiple_of(pair.tick_size) {
+            return Err(OrderError::OffTick);
+        }
+
+        Ok(())
+    }
 
-                    msg::reply(Event::OrderModified(order.clone()), 0).expect("Failed to reply with OrderModified event");
+    fn amend(&mut self, id: OrderId, change: Change, now: u64) {
+        self.amendments.entry(id).or_default().push(Amendment {
+            timestamp: now,
+            change,
+        });
+    }
+
+    /// Removes up to [`MAX_SWEEP`] orders that expired by `now`.
+    fn sweep(&mut self, now: u64) -> Vec<Order> {
+        let expired: Vec<OrderId> = self
+            .expiries
+            .range(..(now + 1, 0))
+            .take(MAX_SWEEP)
+            .map(|(_, id)| *id)
+            .collect();
+
+        expired.iter().filter_map(|id| self.remove(*id)).collect()
+    }
+
+    /// Makes sure a sweep is pending for the earliest expiry. A sweep that
+    /// was due before `now` and never ran, say out of gas, no longer counts.
+    fn schedule_sweep(&mut self, now: u64) {
+        let Some(&(valid_until, _)) = self.expiries.keys().next() else {
+            return;
+        };
+
+        if self
+            .next_sweep
+            .is_some_and(|pending| (now..=valid_until).contains(&pending))
+        {
+            return;
+        }
+
+        let delay = valid_until
+            .saturating_sub(now)
+            .div_ceil(BLOCK_TIME)
+            .try_into()
+            .expect("Expiry too far to schedule");
+
+        msg::send_with_gas_delayed(
+            exec::program_id(),
+            Action::SweepExpired,
+            SWEEP_GAS,
+            0,
+            delay,
+        )
+        .expect("Failed to schedule sweep");
+
+        self.next_sweep = Some(valid_until);
+    }
+
+    /// Trades `taker` against `maker` at `price` for as much as both have
+    /// left. A filled order leaves its book and is locked.
+    fn fill(&mut self, taker: OrderId, maker: OrderId, price: Decimal) -> Fill {
+        let base_amount = self.orders[&taker]
+            .remaining
+            .min(self.orders[&maker].remaining);
+        let quote_amount = base_amount
+            .checked_mul(price)
+            .expect("Fill amount out of range");
+
+        for id in [taker, maker] {
+            let order = self.orders.get_mut(&id).expect("Order not found");
+
+            order.remaining = order
+                .remaining
+                .checked_sub(base_amount)
+                .expect("Fill exceeds remaining quantity");
+
+            if order.remaining.is_zero() {
+                order.is_locked = true;
+
+                let order = order.clone();
+                self.unrest(&order);
+
+                if let Some(arrival) = self.arrivals.get(&id) {
+                    self.expiries.remove(&(order.valid_until, *arrival));
                 }
             }
-        },
-        Action::CheckOrders (test_bool) => {
-            let caller_id = msg::source();
+        }
 
-            unsafe {
-                let user_orders: Vec<Order> = ORDER_BOOK.iter()
-                    .filter(|order| order.user == caller_id)
-                    .cloned()
-                    .collect();
+        let fill = Fill {
+            maker,
+            taker,
+            price,
+            base_amount,
+            quote_amount,
+            timestamp: exec::block_timestamp(),
+        };
 
-                for user_order in &user_orders {
-                    let procceed_match = |match_data: (Order, MatchType)| {
-                        
-                        mark_order_as_matched(addr_of_mut!(ORDER_BOOK), user_order);
-                        mark_order_as_matched(addr_of_mut!(ORDER_BOOK), &match_data.0);
+        for id in [taker, maker] {
+            self.order_fills
+                .entry(id)
+                .or_default()
+                .push(self.fills.len());
+        }
 
-                        let order_pair_alice = OrderPair{n1: match_data.0.clone(), n2: user_order.clone(), role: SwapRole::Alice, match_type: match_data.1.clone()};
+        self.fills.push(fill.clone());
 
-                        let order_pair_bob = OrderPair{n1: match_data.0.clone(), n2: user_order.clone(),  role: SwapRole::Bob, match_type: match_data.1.clone()};
+        fill
+    }
 
-                        msg::send(user_order.user, Event::OrderMatched(order_pair_alice.clone()), 0).expect("Failed to send order to order creator");
+    fn unrest(&mut self, order: &Order) {
+        let (Some(price), Some(arrival)) = (order.price(), self.arrivals.get(&order.id)) else {
+            return;
+        };
 
-                        msg::send(match_data.0.user, Event::OrderMatched(order_pair_bob.clone()), 0).expect("Failed to send matched order to matched order creator");
-                    };
-                        
-                if let Some(matched_order) = find_exact_matching_order(user_order) {
-                    procceed_match(matched_order);
-                } else if let Some(matched_order) = find_exact_matching_order_slippage(user_order) {
-                    procceed_match(matched_order);
-                } else if let Some(matched_order) = find_and_match_partial_order(user_order) {
-                    procceed_match(matched_order);
-                }
+        let pair = order.pair();
 
-                };
-            };
+        if let Some(book) = self.books.get_mut(&pair) {
+            book.remove(order.side(), price, *arrival);
+
+            if book.is_empty() {
+                self.books.remove(&pair);
+            }
         }
     }
+
+    /// Unexpired orders of other users that `taker` can trade with at its
+    /// price, or within its slippage if it is a market order, with their
+    /// price, best price first and oldest first among equal prices.
+    fn matches<'a>(
+        &'a self,
+        taker: &'a Order,
+        now: u64,
+    ) -> impl Iterator<Item = (OrderId, Decimal)> + 'a {
+        let side = taker.side();
+        let limit = match taker.order_type {
+            OrderType::Market => taker.price_limit(),
+            OrderType::Limit | OrderType::PostOnly => taker.price(),
+        }
+        .expect("Order price out of range");
+
+        self.books
+            .get(&taker.pair())
+            .into_iter()
+            .flat_map(move |book| book.iter(side.opposite()))
+            .take_while(move |(maker_price, _)| crosses(side, *maker_price, limit))
+            .map(|(maker_price, id)| (maker_price, &self.orders[id]))
+            .filter(move |(_, maker)| maker.user != taker.user && !is_expired(maker, now))
+            .map(|(maker_price, maker)| (maker.id, maker_price))
+    }
+
+    fn find_match(&self, taker: &Order, now: u64) -> Option<(OrderId, Decimal)> {
+        self.matches(taker, now).next()
+    }
+
+    /// Whether the book holds enough to fill what `taker` has left.
+    fn can_fill(&self, taker: &Order, now: u64) -> bool {
+        let mut left = taker.remaining;
+
+        for (id, _) in self.matches(taker, now) {
+            left = left
+                .checked_sub(self.orders[&id].remaining)
+                .unwrap_or_default();
+
+            if left.is_zero() {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Drops what an immediate order has left after trading: the order
+    /// shrinks to what filled, or goes away if nothing did.
+    fn cancel_rest(&mut self, id: OrderId) -> Option<Order> {
+        let order = self.orders.get(&id)?.clone();
+
+        if order.remaining.is_zero() {
+            return Some(order);
+        }
+
+        if order.remaining == order.quantity {
+            self.remove(id);
+            return None;
+        }
+
+        self.unrest(&order);
+
+        if let Some(arrival) = self.arrivals.get(&id) {
+            self.expiries.remove(&(order.valid_until, *arrival));
+        }
+
+        let order = self.orders.get_mut(&id)?;
+
+        order.quantity = order
+            .quantity
+            .checked_sub(order.remaining)
+            .unwrap_or_default();
+        order.remaining = Decimal::ZERO;
+        order.is_locked = true;
+
+        Some(order.clone())
+    }
+
+    fn fills(&self, id: OrderId) -> Vec<Fill> {
+        self.order_fills
+            .get(&id)
+            .map(|positions| {
+                positions
+                    .iter()
+                    .map(|position| self.fills[*position].clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 }
 
+static mut ORDER_BOOK: Option<OrderBook> = None;
+
 #[no_mangle]
-extern "C" fn state() {
-    unsafe{
-        let caller_id = msg::source();
-        let user_orders: Vec<Order> = ORDER_BOOK.iter()
-            .filter(|order| order.user != caller_id)
-            .cloned()
-            .collect();
+extern "C" fn init() {
+    let init: OrderBookInit = msg::load().expect("Failed to load OrderBookInit");
 
-        msg::reply(user_orders.clone(), 0).expect("Failed to reply with State Orders");
-    }
+    let book = OrderBook {
+        owner: msg::source(),
+        oracle: init.oracle,
+        ..Default::default()
+    };
+
+    unsafe { ORDER_BOOK = Some(book) };
 }
 
-/// Finds an exact match.
-fn find_exact_matching_order(new_order: &Order) -> Option<(Order, MatchType)> {
-    unsafe {
-        for existing_order in &ORDER_BOOK {
-            if existing_order.user != new_order.user &&
-               !existing_order.is_locked && !existing_order.is_inactive &&
-               existing_order.alpha_asset.name == new_order.beta_asset.name &&
-               existing_order.beta_asset.name == new_order.alpha_asset.name &&
-               existing_order.alpha_asset.nominal_amount == new_order.beta_asset.nominal_amount &&
-               existing_order.beta_asset.nominal_amount == new_order.alpha_asset.nominal_amount
-               {
-                   return Some((existing_order.clone(), MatchType::Exact));
+#[gstd::async_main]
+async fn main() {
+    let action: Action = msg::load().expect("Failed to load Action message");
+
+    let book = unsafe { ORDER_BOOK.get_or_insert_with(OrderBook::default) };
+
+    match action {
+        Action::AddOrder(mut order, ttl) => {
+            let ttl = ttl.unwrap_or(DEFAULT_ORDER_TTL);
+
+            let checked = validate_order(&order)
+                .and_then(|()| book.check_listing(&order))
+                .and_then(|()| {
+                    if (MIN_ORDER_TTL..=MAX_ORDER_TTL).contains(&ttl) {
+                        Ok(())
+                    } else {
+                        Err(OrderError::TtlOutOfBounds)
+                    }
+                });
+
+            if let Err(error) = checked {
+                msg::reply(Err::<Event, _>(error), 0).expect("Failed add order");
+                return;
+            }
+
+            order.user = msg::source();
+            order.id = order_id(book.nonce, order.user);
+            order.valid_until = exec::block_timestamp() + ttl;
+            order.is_locked = false;
+            order.quantity = order.base_quantity();
+            order.remaining = order.quantity;
+
+            let now = exec::block_timestamp();
+
+            order.is_inactive = order.trigger.is_some();
+            order.inactive_time_start = if order.is_inactive { now } else { 0 };
+
+            let rejection = match (order.order_type, order.time_in_force) {
+                _ if order.is_inactive => None,
+                (OrderType::PostOnly, _) if book.find_match(&order, now).is_some() => {
+                    Some(OrderError::WouldCross)
+                }
+                (_, TimeInForce::FillOrKill) if !book.can_fill(&order, now) => {
+                    Some(OrderError::Unfilled)
                 }
+                _ => None,
+            };
+
+            if let Some(error) = rejection {
+                msg::reply(Err::<Event, _>(error), 0).expect("Failed add order");
+                return;
+            }
+
+            let id = order.id;
+
+            book.nonce += 1;
+            book.insert(order);
+
+            // Trade what crosses right away, so only the rest is left in the
+            // book. Stop orders wait for their trigger.
+            take(book, id, now);
+
+            let order = &book.orders[&id];
+
+            let result = if !order.is_inactive && order_is_immediate(order) {
+                book.cancel_rest(id).ok_or(OrderError::Unfilled)
+            } else {
+                Ok(order.clone())
+            };
+
+            book.schedule_sweep(now);
+
+            msg::reply(result.map(Event::OrderAdded), 0).expect("Failed add order");
+        }
+        Action::DeleteOrder(id) => {
+            let result = book
+                .delete(id, msg::source(), exec::block_timestamp())
+                .map(|()| Event::OrderDeleted(id));
+
+            msg::reply(result, 0).expect("Failed to reply with OrderDeleted event");
+        }
+        Action::ModifyOrder(id, update) => {
+            let now = exec::block_timestamp();
+
+            let result = book.modify(id, msg::source(), update, now).map(|order| {
+                // A new price may cross the book: trade that part right away,
+                // as on arrival.
+                take(book, order.id, now);
+
+                Event::OrderModified(book.orders[&order.id].clone())
+            });
+
+            msg::reply(result, 0).expect("Failed to reply with OrderModified event");
+        }
+        Action::CheckOrders(_) => {
+            let caller_id = msg::source();
+            let now = exec::block_timestamp();
+
+            let user_orders: Vec<OrderId> = book
+                .orders
+                .values()
+                .filter(|order| order.user == caller_id)
+                .map(|order| order.id)
+                .collect();
+
+            for id in user_orders {
+                if book.orders[&id].order_type != OrderType::PostOnly {
+                    take(book, id, now);
+                }
+            }
+        }
+        Action::Poke(asset) => {
+            let result = match oracle_price(&asset.symbol).await {
+                Ok(price) => {
+                    // Other messages may have run while the oracle answered.
+                    let book = unsafe { ORDER_BOOK.get_or_insert_with(OrderBook::default) };
+                    let triggered = trigger_stops(book, &asset, price, exec::block_timestamp());
+
+                    Ok(Event::StopsTriggered(triggered))
+                }
+                Err(error) => Err(error),
+            };
+
+            msg::reply(result, 0).expect("Failed to reply with StopsTriggered event");
+        }
+        Action::PriceUpdate(asset, price) => {
+            let result = if book.oracle == Some(msg::source()) {
+                let triggered = trigger_stops(book, &asset, price, exec::block_timestamp());
+
+                Ok(Event::StopsTriggered(triggered))
+            } else {
+                Err(OrderError::NotAllowed)
+            };
+
+            msg::reply(result, 0).expect("Failed to reply with StopsTriggered event");
+        }
+        Action::SetOracle(oracle) => {
+            let result = if msg::source() == book.owner {
+                book.oracle = Some(oracle);
+                Ok(Event::OracleSet(oracle))
+            } else {
+                Err(OrderError::NotAllowed)
+            };
+
+            msg::reply(result, 0).expect("Failed to reply with OracleSet event");
+        }
+        Action::RegisterAsset(key, info) => {
+            let result = if msg::source() != book.owner {
+                Err(OrderError::NotAllowed)
+            } else if u32::from(info.decimals) > DECIMALS {
+                Err(OrderError::TooManyDecimals)
+            } else {
+                book.assets.insert(key.clone(), info);
+                Ok(Event::AssetRegistered(key))
+            };
+
+            msg::reply(result, 0).expect("Failed to reply with AssetRegistered event");
+        }
+        Action::ListPair(a, b, info) => {
+            let result = if msg::source() != book.owner {
+                Err(OrderError::NotAllowed)
+            } else if a == b {
+                Err(OrderError::SameAsset)
+            } else if !book.assets.contains_key(&a) || !book.assets.contains_key(&b) {
+                Err(OrderError::UnknownAsset)
+            } else {
+                let pair = Pair::new(a, b);
+
+                book.pairs.insert(pair.clone(), info);
+                Ok(Event::PairListed(pair))
+            };
+
+            msg::reply(result, 0).expect("Failed to reply with PairListed event");
+        }
+        Action::DelistPair(pair) => {
+            let result = if msg::source() != book.owner {
+                Err(OrderError::NotAllowed)
+            } else if book.pairs.remove(&pair).is_none() {
+                Err(OrderError::UnknownPair)
+            } else {
+                Ok(Event::PairDelisted(pair))
+            };
+
+            msg::reply(result, 0).expect("Failed to reply with PairDelisted event");
+        }
+        Action::SweepExpired => {
+            assert!(
+                msg::source() == exec::program_id(),
+                "Only the program can sweep expired orders"
+            );
+
+            let now = exec::block_timestamp();
+
+            // Even when this sweep came early, the one scheduled below
+            // takes over from whatever was pending.
+            book.next_sweep = None;
+
+            for order in book.sweep(now) {
+                msg::send(order.user, Event::OrderExpired(order.id), 0)
+                    .expect("Failed to send OrderExpired event");
+            }
+
+            book.schedule_sweep(now);
         }
     }
-    None
 }
 
-/// Finds a matching order with slippage.
-fn find_exact_matching_order_slippage(new_order: &Order) -> Option<(Order, MatchType)> {
-    unsafe {
-        let new_order_max_acceptable_price = I16F16::lit(&new_order.alpha_asset_price) * (I16F16::lit(&"1") + I16F16::lit(&new_order.user_slippage) / 100);
-        let new_order_min_acceptable_price = I16F16::lit(&new_order.alpha_asset_price) * (I16F16::lit(&"1") - I16F16::lit(&new_order.user_slippage) / 100);
+/// Asks the oracle for the latest price of `asset`.
+async fn oracle_price(asset: &str) -> Result<Decimal, OrderError> {
+    let book = unsafe { ORDER_BOOK.get_or_insert_with(OrderBook::default) };
+    let oracle = book.oracle.ok_or(OrderError::NoOracle)?;
+
+    let reply: OracleEvent = msg::send_for_reply_as(
+        oracle,
+        OracleAction::GetPrices {
+            crypto: asset.into(),
+        },
+        0,
+        0,
+    )
+    .expect("Unable to query oracle")
+    .await
+    .expect("Unable to decode oracle reply");
+
+    match reply {
+        OracleEvent::Prices(Some(prices)) => prices
+            .minute
+            .parse::<Decimal>()
+            .ok()
+            .filter(|price| !price.is_zero())
+            .ok_or(OrderError::NoPrice),
+        _ => Err(OrderError::NoPrice),
+    }
+}
 
-        for existing_order in &ORDER_BOOK {
+/// Activates up to [`MAX_TRIGGER`] unexpired stop orders on `asset` that
+/// `price` meets. They trade right away, and immediate ones drop what they
+/// could not fill.
+fn trigger_stops(book: &mut OrderBook, asset: &AssetKey, price: Decimal, now: u64) -> Vec<OrderId> {
+    let triggered: Vec<OrderId> = book
+        .stops
+        .get(asset)
+        .map(|stops| {
+            stops
+                .triggered(price)
+                .filter(|id| !is_expired(&book.orders[id], now))
+                .take(MAX_TRIGGER)
+                .collect()
+        })
+        .unwrap_or_default();
 
-            let existing_order_max_acceptable_price = I16F16::lit(&existing_order.alpha_asset_price) * (I16F16::lit(&"1") + I16F16::lit(&existing_order.user_slippage) / 100);
-            let existing_order_min_acceptable_price = I16F16::lit(&existing_order.alpha_asset_price) * (I16F16::lit(&"1") - I16F16::lit(&existing_order.user_slippage) / 100);
+    for &id in &triggered {
+        book.activate(id);
 
-            if existing_order.user != new_order.user &&
-               !existing_order.is_locked &&
-               existing_order.alpha_asset.name == new_order.beta_asset.name &&
-               existing_order.beta_asset.name == new_order.alpha_asset.name &&
+        let order = &book.orders[&id];
+        let user = order.user;
 
-               new_order_max_acceptable_price >= existing_order_min_acceptable_price &&
-               new_order_min_acceptable_price <= existing_order_max_acceptable_price
-               {
-                   return Some((existing_order.clone(), MatchType::WithSlippage));
-               }
+        let immediate = order_is_immediate(order);
+
+        msg::send(user, Event::OrderTriggered(id), 0).expect("Failed to send OrderTriggered event");
+
+        let fillable = order.time_in_force != TimeInForce::FillOrKill || book.can_fill(order, now);
+
+        if fillable {
+            take(book, id, now);
+        }
+
+        if !immediate {
+            continue;
+        }
+
+        if !fillable || book.cancel_rest(id).is_none() {
+            book.remove(id);
+
+            msg::send(user, Event::OrderCancelled(id), 0)
+                .expect("Failed to send OrderCancelled event");
         }
     }
-    None
+
+    triggered
 }
 
-/// Finds and matches a partial order if the new order is smaller than an existing order.
-fn find_and_match_partial_order(new_order: &Order) -> Option<(Order, MatchType)> {
-    unsafe {
-        for existing_order in ORDER_BOOK.iter_mut() {
-            if existing_order.user != new_order.user &&
-               !existing_order.is_locked &&
-               existing_order.alpha_asset.name == new_order.beta_asset.name &&
-               existing_order.beta_asset.name == new_order.alpha_asset.name &&
-
-               (existing_order.alpha_asset.nominal_amount > new_order.beta_asset.nominal_amount &&
-               existing_order.beta_asset.nominal_amount > new_order.alpha_asset.nominal_amount) ||
-
-               (existing_order.alpha_asset.nominal_amount < new_order.beta_asset.nominal_amount &&
-               existing_order.beta_asset.nominal_amount < new_order.alpha_asset.nominal_amount)
-               {
-                    return Some((existing_order.clone(), MatchType::Partial))
-               }
-            }
+/// Trades order `id` against the book until it is filled or nothing it can
+/// trade with is left, and tells both sides of every fill.
+fn take(book: &mut OrderBook, id: OrderId, now: u64) {
+    loop {
+        let user_order = &book.orders[&id];
+
+        if user_order.is_locked || user_order.is_inactive || is_expired(user_order, now) {
+            break;
         }
-    None
+
+        let Some((matched_id, price)) = book.find_match(user_order, now) else {
+            break;
+        };
+
+        let within_limit = crosses(
+            user_order.side(),
+            price,
+            user_order.price().expect("Order price out of range"),
+        );
+
+        let fill = book.fill(id, matched_id, price);
+
+        let user_order = book.orders[&id].clone();
+        let matched_order = book.orders[&matched_id].clone();
+
+        let match_type = if !user_order.remaining.is_zero() || !matched_order.remaining.is_zero() {
+            MatchType::Partial
+        } else if !within_limit {
+            MatchType::WithSlippage
+        } else {
+            MatchType::Exact
+        };
+
+        let order_pair_alice = OrderPair {
+            n1: matched_order.clone(),
+            n2: user_order.clone(),
+            role: SwapRole::Alice,
+            match_type: match_type.clone(),
+            fill: fill.clone(),
+        };
+
+        let order_pair_bob = OrderPair {
+            n1: matched_order.clone(),
+            n2: user_order.clone(),
+            role: SwapRole::Bob,
+            match_type,
+            fill,
+        };
+
+        msg::send(user_order.user, Event::OrderMatched(order_pair_alice), 0)
+            .expect("Failed to send order to order creator");
+
+        msg::send(matched_order.user, Event::OrderMatched(order_pair_bob), 0)
+            .expect("Failed to send matched order to matched order creator");
+    }
 }
 
-/// Marks an order as matched.
-fn mark_order_as_matched(order_book: *mut Vec<Order>, order: &Order) {
-    unsafe {
-        if !order_book.is_null() {
-            let order_book_ref: &mut Vec<Order> = &mut *order_book;
-            for o in order_book_ref.iter_mut() {
-                if o.id == order.id {
-                    o.is_locked = true;
-                    break;
-                }
-            }
+#[no_mangle]
+extern "C" fn state() {
+    let query: StateQuery = msg::load().expect("Failed to load StateQuery");
+
+    let book = unsafe { ORDER_BOOK.get_or_insert_with(OrderBook::default) };
+
+    let reply = match query {
+        StateQuery::Orders => {
+            let caller_id = msg::source();
+
+            StateReply::Orders(
+                book.orders
+                    .values()
+                    .filter(|order| order.user != caller_id)
+                    .cloned()
+                    .collect(),
+            )
         }
-    }
+        StateQuery::Fills(id) => StateReply::Fills(book.fills(id)),
+        StateQuery::Nonce => StateReply::Nonce(book.nonce),
+        StateQuery::Amendments(id) => {
+            StateReply::Amendments(book.amendments.get(&id).cloned().unwrap_or_default())
+        }
+        StateQuery::Assets => StateReply::Assets(
+            book.assets
+                .iter()
+                .map(|(key, info)| (key.clone(), *info))
+                .collect(),
+        ),
+        StateQuery::Pairs => StateReply::Pairs(
+            book.pairs
+                .iter()
+                .map(|(pair, info)| (pair.clone(), *info))
+                .collect(),
+        ),
+    };
+
+    msg::reply(reply, 0).expect("Failed to reply with State Orders");
+}
+
+/// Whether the order trades only on arrival and never rests.
+fn order_is_immediate(order: &Order) -> bool {
+    order.time_in_force != TimeInForce::GoodTillCancelled
 }
 
-fn sha2_256(data: &[u8]) -> [u8; 32] {
-    let mut output = [0u8; 32];
-    output.copy_from_slice(sha2::Sha256::digest(data).as_slice());
-    output
+fn is_expired(order: &Order, now: u64) -> bool {
+    order.valid_until <= now
 }
 
-pub fn encode_hex(bytes: &[u8]) -> String {
-    let mut s = String::with_capacity(bytes.len() * 2);
-    for &b in bytes {
-        write!(&mut s, "{:02x}", b).unwrap();
+/// Whether a `side` order limited to `limit` trades at `price`.
+fn crosses(side: Side, price: Decimal, limit: Decimal) -> bool {
+    match side {
+        Side::Bid => price <= limit,
+        Side::Ask => price >= limit,
     }
-    s
-}
\ No newline at end of file
+}
+
+/// Checks that the order's assets, amounts, prices and slippage can be
+/// matched on, and that its type, time in force and trigger go together.
+fn validate_order(order: &Order) -> Result<(), OrderError> {
+    let (alpha, beta) = (&order.alpha_asset, &order.beta_asset);
+    let trigger = order.trigger.as_ref();
+
+    let checks = [
+        alpha.key() != beta.key(),
+        !alpha.nominal_amount.is_zero() && !beta.nominal_amount.is_zero(),
+        !order.alpha_asset_price.is_zero() && !order.beta_asset_price.is_zero(),
+        order.user_slippage <= Decimal::HUNDRED,
+        // Market orders never rest, post-only ones always do.
+        order.order_type != OrderType::Market
+            || order.time_in_force != TimeInForce::GoodTillCancelled,
+        order.order_type != OrderType::PostOnly
+            || order.time_in_force == TimeInForce::GoodTillCancelled,
+        trigger.is_none() || order.order_type != OrderType::PostOnly,
+        // A trigger watches a non-zero price of one of the order's assets.
+        !trigger.is_some_and(|trigger| trigger.price.is_zero()),
+        !trigger.is_some_and(|trigger| trigger.asset != alpha.key() && trigger.asset != beta.key()),
+        order.price_limit().is_some(),
+    ];
+
+    if checks.contains(&false) {
+        return Err(OrderError::InvalidOrder);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+//! Helpers shared by the program tests. Each test binary uses some of them.
+#![allow(dead_code)]
+
+use gstd::{codec::Decode, ActorId};
+use gtest::{Program, RunResult, System};
+use order_book_io::{
+    Action, Asset, AssetInfo, AssetKey, Decimal, Event, Fill, Order, OrderBookInit, OrderError,
+    OrderId, PairInfo, StateQuery, StateReply,
+};
+
+pub const OWNER: u64 = 1;
+pub const ALICE: u64 = 10;
+pub const BOB: u64 = 11;
+pub const CAROL: u64 = 12;
+
+/// Base asset of the listed pair.
+pub const BASE: &str = "BTC";
+/// Quote asset of the listed pair.
+pub const QUOTE: &str = "USDT";
+
+/// Initialises the order book from `OWNER`, registers `BASE` and `QUOTE`
+/// and lists their pair with a tick of 0.01.
+pub fn deploy(system: &System, oracle: Option<ActorId>) -> Program<'_> {
+    system.init_logger();
+
+    for user in [OWNER, ALICE, BOB, CAROL] {
+        system.mint_to(user, 1_000_000_000_000_000);
+    }
+
+    let program = Program::current_opt(system);
+    assert!(!program.send(OWNER, OrderBookInit { oracle }).main_failed());
+
+    let info = AssetInfo {
+        decimals: 8,
+        min_size: decimal("0.0001"),
+    };
+
+    for symbol in [BASE, QUOTE] {
+        let result = program.send(OWNER, Action::RegisterAsset(key(symbol), info));
+        assert!(reply(&result, OWNER).is_ok());
+    }
+
+    let result = program.send(
+        OWNER,
+        Action::ListPair(
+            key(BASE),
+            key(QUOTE),
+            PairInfo {
+                tick_size: decimal("0.01"),
+            },
+        ),
+    );
+    assert!(reply(&result, OWNER).is_ok());
+
+    program
+}
+
+pub fn decimal(s: &str) -> Decimal {
+    s.parse().expect("Invalid decimal")
+}
+
+pub fn key(symbol: &str) -> AssetKey {
+    AssetKey {
+        symbol: symbol.into(),
+        ..Default::default()
+    }
+}
+
+/// Limit order selling `amount` of `BASE` at `price` in `QUOTE`.
+pub fn ask(amount: &str, price: &str) -> Order {
+    let quote = decimal(amount)
+        .checked_mul(decimal(price))
+        .expect("Order amount out of range");
+
+    order(BASE, decimal(amount), QUOTE, quote)
+}
+
+/// Limit order buying `amount` of `BASE` at `price` in `QUOTE`.
+pub fn bid(amount: &str, price: &str) -> Order {
+    let quote = decimal(amount)
+        .checked_mul(decimal(price))
+        .expect("Order amount out of range");
+
+    order(QUOTE, quote, BASE, decimal(amount))
+}
+
+fn order(sell: &str, sell_amount: Decimal, buy: &str, buy_amount: Decimal) -> Order {
+    let asset = |name: &str, nominal_amount| Asset {
+        name: name.into(),
+        nominal_amount,
+        ..Default::default()
+    };
+
+    Order {
+        alpha_asset: asset(sell, sell_amount),
+        beta_asset: asset(buy, buy_amount),
+        alpha_asset_price: Decimal::ONE,
+        beta_asset_price: Decimal::ONE,
+        ..Default::default()
+    }
+}
+
+/// Adds `order` from `user` with the default time-to-live, and returns it as
+/// the program stored it.
+pub fn add(program: &Program, user: u64, order: Order) -> Result<Order, OrderError> {
+    added(&program.send(user, Action::AddOrder(order, None)), user)
+}
+
+pub fn added(result: &RunResult, user: u64) -> Result<Order, OrderError> {
+    match reply(result, user)? {
+        Event::OrderAdded(order) => Ok(order),
+        event => panic!("Unexpected reply {event:?}"),
+    }
+}
+
+pub fn reply(result: &RunResult, to: u64) -> Result<Event, OrderError> {
+    let to = ActorId::from(to);
+
+    result
+        .log()
+        .iter()
+        .filter(|log| log.reply_code().is_some() && log.destination().as_ref() == to.as_ref())
+        .find_map(|log| Result::<Event, OrderError>::decode(&mut log.payload()).ok())
+        .expect("No reply")
+}
+
+/// Events the program sent to `to` while handling `result`, other than the
+/// reply.
+pub fn events(result: &RunResult, to: u64) -> Vec<Event> {
+    let to = ActorId::from(to);
+
+    result
+        .log()
+        .iter()
+        .filter(|log| log.reply_code().is_none() && log.destination().as_ref() == to.as_ref())
+        .filter_map(|log| Event::decode(&mut log.payload()).ok())
+        .collect()
+}
+
+/// Order `id` as the program holds it, if it still does.
+pub fn find(program: &Program, id: OrderId) -> Option<Order> {
+    let StateReply::Orders(orders) = state(program, StateQuery::Orders) else {
+        panic!("Unexpected state reply");
+    };
+
+    orders.into_iter().find(|order| order.id == id)
+}
+
+pub fn fills(program: &Program, id: OrderId) -> Vec<Fill> {
+    let StateReply::Fills(fills) = state(program, StateQuery::Fills(id)) else {
+        panic!("Unexpected state reply");
+    };
+
+    fills
+}
+
+/// Maker, price and base amount of every fill of order `id`, oldest first.
+pub fn trades(program: &Program, id: OrderId) -> Vec<(OrderId, Decimal, Decimal)> {
+    fills(program, id)
+        .into_iter()
+        .map(|fill| (fill.maker, fill.price, fill.base_amount))
+        .collect()
+}
+
+pub fn state(program: &Program, query: StateQuery) -> StateReply {
+    program.read_state(query).expect("Failed to read state")
+}
+
+pub fn actor(program: &Program) -> ActorId {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(program.id().as_ref());
+    ActorId::from(bytes)
+}
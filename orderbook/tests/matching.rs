@@ -0,0 +1,187 @@
+mod common;
+
+use common::*;
+use gtest::System;
+use order_book_io::{Action, Event, MatchType};
+
+#[test]
+fn best_price_trades_first() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let dear = add(&program, ALICE, ask("1", "101")).unwrap();
+    let cheap = add(&program, BOB, ask("1", "100")).unwrap();
+
+    // The cheapest ask fills, at its own price.
+    let taker = add(&program, CAROL, bid("1", "101")).unwrap();
+    assert_eq!(
+        trades(&program, taker.id),
+        [(cheap.id, decimal("100"), decimal("1"))]
+    );
+    assert!(taker.remaining.is_zero() && taker.is_locked);
+    assert_eq!(find(&program, dear.id).unwrap().remaining, decimal("1"));
+
+    // And the highest bid.
+    let low = add(&program, ALICE, bid("1", "98")).unwrap();
+    let high = add(&program, BOB, bid("1", "99")).unwrap();
+
+    let taker = add(&program, CAROL, ask("1", "98")).unwrap();
+    assert_eq!(
+        trades(&program, taker.id),
+        [(high.id, decimal("99"), decimal("1"))]
+    );
+    assert_eq!(find(&program, low.id).unwrap().remaining, decimal("1"));
+}
+
+#[test]
+fn equal_prices_trade_in_arrival_order() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let first = add(&program, ALICE, ask("1", "100")).unwrap();
+    let second = add(&program, BOB, ask("1", "100")).unwrap();
+    let third = add(&program, ALICE, ask("1", "100")).unwrap();
+
+    for maker in [first.id, second.id, third.id] {
+        let taker = add(&program, CAROL, bid("1", "100")).unwrap();
+
+        assert_eq!(
+            trades(&program, taker.id),
+            [(maker, decimal("100"), decimal("1"))]
+        );
+    }
+}
+
+#[test]
+fn takers_sweep_across_levels() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let makers = [
+        add(&program, ALICE, ask("1", "100")).unwrap(),
+        add(&program, BOB, ask("1", "101")).unwrap(),
+        add(&program, ALICE, ask("1", "102")).unwrap(),
+    ];
+    let beyond = add(&program, BOB, ask("1", "103")).unwrap();
+
+    let result = program.send(CAROL, Action::AddOrder(bid("2.5", "102"), None));
+    let taker = added(&result, CAROL).unwrap();
+
+    // Level by level up to its price, each at the maker's price.
+    assert_eq!(
+        trades(&program, taker.id),
+        [
+            (makers[0].id, decimal("100"), decimal("1")),
+            (makers[1].id, decimal("101"), decimal("1")),
+            (makers[2].id, decimal("102"), decimal("0.5")),
+        ]
+    );
+    assert!(taker.remaining.is_zero() && taker.is_locked);
+
+    let quotes: Vec<_> = events(&result, CAROL)
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::OrderMatched(pair) => Some(pair.fill.quote_amount),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(quotes, [decimal("100"), decimal("101"), decimal("51")]);
+
+    assert_eq!(
+        find(&program, makers[2].id).unwrap().remaining,
+        decimal("0.5")
+    );
+    assert!(trades(&program, beyond.id).is_empty());
+}
+
+#[test]
+fn partial_fills_leave_the_rest() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let maker = add(&program, ALICE, ask("2", "100")).unwrap();
+
+    let result = program.send(BOB, Action::AddOrder(bid("0.5", "100"), None));
+    let first = added(&result, BOB).unwrap();
+    assert!(first.remaining.is_zero() && first.is_locked);
+
+    // The maker keeps resting for what is left.
+    let rest = find(&program, maker.id).unwrap();
+    assert_eq!(rest.quantity, decimal("2"));
+    assert_eq!(rest.remaining, decimal("1.5"));
+    assert!(!rest.is_locked);
+
+    // Both sides hear of the fill, and it is partial for the maker.
+    for user in [ALICE, BOB] {
+        let [Event::OrderMatched(pair)] = &events(&result, user)[..] else {
+            panic!("expected one match for {user}");
+        };
+        assert_eq!(pair.match_type, MatchType::Partial);
+        assert_eq!((pair.n1.id, pair.n2.id), (maker.id, first.id));
+        assert_eq!(pair.fill, fills(&program, first.id)[0]);
+    }
+
+    // A larger taker fills the rest and rests for its own.
+    let second = add(&program, CAROL, bid("2", "100")).unwrap();
+    assert_eq!(second.remaining, decimal("0.5"));
+    assert!(!second.is_locked);
+
+    let done = find(&program, maker.id).unwrap();
+    assert!(done.remaining.is_zero() && done.is_locked);
+
+    // Every order keeps its fills, oldest first.
+    let maker_fills = fills(&program, maker.id);
+    assert_eq!(maker_fills.len(), 2);
+
+    for (fill, (taker, base, quote)) in maker_fills
+        .iter()
+        .zip([(first.id, "0.5", "50"), (second.id, "1.5", "150")])
+    {
+        assert_eq!((fill.maker, fill.taker), (maker.id, taker));
+        assert_eq!(fill.price, decimal("100"));
+        assert_eq!(fill.base_amount, decimal(base));
+        assert_eq!(fill.quote_amount, decimal(quote));
+    }
+
+    assert_eq!(fills(&program, first.id), maker_fills[..1]);
+    assert_eq!(fills(&program, second.id), maker_fills[1..]);
+}
+
+#[test]
+fn own_orders_are_skipped() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let own = add(&program, ALICE, ask("1", "100")).unwrap();
+    let other = add(&program, BOB, ask("1", "101")).unwrap();
+
+    // Alice's bid passes over her own ask to Bob's, and only what it could
+    // not fill rests.
+    let taker = add(&program, ALICE, bid("2", "101")).unwrap();
+    assert_eq!(
+        trades(&program, taker.id),
+        [(other.id, decimal("101"), decimal("1"))]
+    );
+
+    let rest = find(&program, taker.id).unwrap();
+    assert_eq!(rest.quantity, decimal("2"));
+    assert_eq!(rest.remaining, decimal("1"));
+    assert!(!rest.is_locked);
+
+    let own = find(&program, own.id).unwrap();
+    assert_eq!(own.remaining, decimal("1"));
+    assert!(trades(&program, own.id).is_empty());
+
+    // Others still trade with both, the best price first.
+    let seller = add(&program, CAROL, ask("1", "100")).unwrap();
+    assert_eq!(
+        trades(&program, seller.id),
+        [(taker.id, decimal("101"), decimal("1"))]
+    );
+
+    let buyer = add(&program, CAROL, bid("1", "100")).unwrap();
+    assert_eq!(
+        trades(&program, buyer.id),
+        [(own.id, decimal("100"), decimal("1"))]
+    );
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use common::*;
+use gtest::System;
+use order_book_io::{Action, Event, MatchType, Order, OrderError, OrderType, TimeInForce};
+
+fn typed(mut order: Order, order_type: OrderType, time_in_force: TimeInForce) -> Order {
+    order.order_type = order_type;
+    order.time_in_force = time_in_force;
+    order
+}
+
+fn with_slippage(mut order: Order, percent: &str) -> Order {
+    order.user_slippage = decimal(percent);
+    order
+}
+
+#[test]
+fn limit_orders_trade_only_at_their_price() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let maker = add(&program, ALICE, ask("1", "101")).unwrap();
+
+    // Slippage does not stretch a limit order past its price.
+    let resting = add(&program, BOB, with_slippage(bid("1", "100"), "5")).unwrap();
+    assert!(trades(&program, resting.id).is_empty());
+    assert_eq!(find(&program, resting.id).unwrap().remaining, decimal("1"));
+
+    let taker = add(&program, CAROL, with_slippage(bid("1", "101"), "5")).unwrap();
+    assert_eq!(
+        trades(&program, taker.id),
+        [(maker.id, decimal("101"), decimal("1"))]
+    );
+}
+
+#[test]
+fn market_orders_trade_within_their_slippage() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let near = add(&program, ALICE, ask("1", "104")).unwrap();
+    let far = add(&program, ALICE, ask("1", "106")).unwrap();
+
+    // Up to 105 with 5 percent on 100: the first ask fills, the rest of the
+    // order is dropped.
+    let market = with_slippage(bid("2", "100"), "5");
+    let result = program.send(
+        BOB,
+        Action::AddOrder(
+            typed(market, OrderType::Market, TimeInForce::ImmediateOrCancel),
+            None,
+        ),
+    );
+    let taker = added(&result, BOB).unwrap();
+
+    assert_eq!(
+        trades(&program, taker.id),
+        [(near.id, decimal("104"), decimal("1"))]
+    );
+    assert_eq!(taker.quantity, decimal("1"));
+    assert!(taker.remaining.is_zero() && taker.is_locked);
+    assert_eq!(find(&program, far.id).unwrap().remaining, decimal("1"));
+
+    // Fill-or-kill needs the whole quantity within its slippage.
+    let market = with_slippage(bid("1", "100"), "5");
+    assert_eq!(
+        add(
+            &program,
+            BOB,
+            typed(market, OrderType::Market, TimeInForce::FillOrKill)
+        ),
+        Err(OrderError::Unfilled)
+    );
+
+    let market = with_slippage(bid("1", "101"), "5");
+    let result = program.send(
+        BOB,
+        Action::AddOrder(
+            typed(market, OrderType::Market, TimeInForce::FillOrKill),
+            None,
+        ),
+    );
+    let taker = added(&result, BOB).unwrap();
+    assert_eq!(
+        trades(&program, taker.id),
+        [(far.id, decimal("106"), decimal("1"))]
+    );
+
+    let [Event::OrderMatched(pair)] = &events(&result, BOB)[..] else {
+        panic!("expected one match");
+    };
+    assert_eq!(pair.match_type, MatchType::WithSlippage);
+}
+
+#[test]
+fn fill_or_kill_trades_all_or_nothing() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let maker = add(&program, ALICE, ask("1", "100")).unwrap();
+
+    let too_large = typed(bid("2", "100"), OrderType::Limit, TimeInForce::FillOrKill);
+    assert_eq!(add(&program, BOB, too_large), Err(OrderError::Unfilled));
+    assert!(trades(&program, maker.id).is_empty());
+
+    let taker = add(
+        &program,
+        BOB,
+        typed(bid("1", "100"), OrderType::Limit, TimeInForce::FillOrKill),
+    )
+    .unwrap();
+    assert!(taker.remaining.is_zero() && taker.is_locked);
+    assert_eq!(
+        trades(&program, taker.id),
+        [(maker.id, decimal("100"), decimal("1"))]
+    );
+}
+
+#[test]
+fn immediate_or_cancel_drops_the_rest() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let maker = add(&program, ALICE, ask("1", "100")).unwrap();
+
+    let taker = add(
+        &program,
+        BOB,
+        typed(
+            bid("3", "100"),
+            OrderType::Limit,
+            TimeInForce::ImmediateOrCancel,
+        ),
+    )
+    .unwrap();
+
+    // The order shrinks to what filled and never rests.
+    assert_eq!(taker.quantity, decimal("1"));
+    assert!(taker.remaining.is_zero() && taker.is_locked);
+    assert_eq!(
+        trades(&program, taker.id),
+        [(maker.id, decimal("100"), decimal("1"))]
+    );
+
+    // With nothing to trade with, nothing is left of it.
+    assert_eq!(
+        add(
+            &program,
+            BOB,
+            typed(
+                bid("1", "100"),
+                OrderType::Limit,
+                TimeInForce::ImmediateOrCancel
+            )
+        ),
+        Err(OrderError::Unfilled)
+    );
+}
+
+#[test]
+fn post_only_orders_never_take() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let maker = add(&program, ALICE, ask("1", "100")).unwrap();
+
+    let post_only = |order| typed(order, OrderType::PostOnly, TimeInForce::GoodTillCancelled);
+
+    assert_eq!(
+        add(&program, BOB, post_only(bid("1", "100"))),
+        Err(OrderError::WouldCross)
+    );
+    assert!(trades(&program, maker.id).is_empty());
+
+    // Its slippage does not make it cross either.
+    let resting = add(&program, BOB, post_only(with_slippage(bid("1", "99"), "5"))).unwrap();
+    assert_eq!(find(&program, resting.id).unwrap().remaining, decimal("1"));
+    assert!(trades(&program, resting.id).is_empty());
+}
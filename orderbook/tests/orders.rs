@@ -0,0 +1,163 @@
+mod common;
+
+use common::*;
+use gstd::ActorId;
+use gtest::System;
+use order_book_io::{
+    order_id, Action, Decimal, OrderError, OrderType, OrderUpdate, StateQuery, StateReply,
+    TimeInForce, Trigger, TriggerCondition, MAX_ORDER_TTL, MIN_ORDER_TTL,
+};
+
+#[test]
+fn invalid_orders_are_refused() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let mut same_asset = ask("1", "100");
+    same_asset.beta_asset.name = BASE.into();
+
+    let mut zero_amount = ask("1", "100");
+    zero_amount.alpha_asset.nominal_amount = Decimal::ZERO;
+
+    let mut zero_price = ask("1", "100");
+    zero_price.beta_asset_price = Decimal::ZERO;
+
+    let mut slippage = ask("1", "100");
+    slippage.user_slippage = decimal("100.5");
+
+    let mut resting_market = bid("1", "100");
+    resting_market.order_type = OrderType::Market;
+
+    let mut immediate_post_only = bid("1", "100");
+    immediate_post_only.order_type = OrderType::PostOnly;
+    immediate_post_only.time_in_force = TimeInForce::ImmediateOrCancel;
+
+    let trigger = Trigger {
+        asset: key(BASE),
+        price: decimal("90"),
+        condition: TriggerCondition::AtOrBelow,
+    };
+
+    let mut post_only_stop = ask("1", "100");
+    post_only_stop.order_type = OrderType::PostOnly;
+    post_only_stop.trigger = Some(trigger.clone());
+
+    let mut zero_trigger = ask("1", "100");
+    zero_trigger.trigger = Some(Trigger {
+        price: Decimal::ZERO,
+        ..trigger.clone()
+    });
+
+    let mut foreign_trigger = ask("1", "100");
+    foreign_trigger.trigger = Some(Trigger {
+        asset: key("ETH"),
+        ..trigger
+    });
+
+    for order in [
+        same_asset,
+        zero_amount,
+        zero_price,
+        slippage,
+        resting_market,
+        immediate_post_only,
+        post_only_stop,
+        zero_trigger,
+        foreign_trigger,
+    ] {
+        let result = program.send(ALICE, Action::AddOrder(order, None));
+
+        assert!(!result.main_failed());
+        assert_eq!(added(&result, ALICE), Err(OrderError::InvalidOrder));
+    }
+
+    for ttl in [0, MIN_ORDER_TTL - 1, MAX_ORDER_TTL + 1] {
+        let result = program.send(ALICE, Action::AddOrder(ask("1", "100"), Some(ttl)));
+
+        assert_eq!(added(&result, ALICE), Err(OrderError::TtlOutOfBounds));
+    }
+
+    for ttl in [MIN_ORDER_TTL, MAX_ORDER_TTL] {
+        let result = program.send(ALICE, Action::AddOrder(ask("1", "100"), Some(ttl)));
+
+        assert!(added(&result, ALICE).is_ok());
+    }
+
+    // Refused orders take no nonce.
+    assert_eq!(state(&program, StateQuery::Nonce), StateReply::Nonce(2));
+}
+
+#[test]
+fn invalid_updates_are_refused() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let order = add(&program, ALICE, ask("1", "100")).unwrap();
+
+    for update in [
+        OrderUpdate {
+            alpha_amount: Some(Decimal::ZERO),
+            ..Default::default()
+        },
+        OrderUpdate {
+            user_slippage: Some(decimal("101")),
+            ..Default::default()
+        },
+    ] {
+        let result = program.send(ALICE, Action::ModifyOrder(order.id, update));
+
+        assert!(!result.main_failed());
+        assert_eq!(reply(&result, ALICE), Err(OrderError::InvalidOrder));
+    }
+
+    assert_eq!(find(&program, order.id), Some(order));
+}
+
+#[test]
+fn ownership_is_checked_before_the_update() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let order = add(&program, ALICE, ask("1", "100")).unwrap();
+
+    let invalid = OrderUpdate {
+        alpha_amount: Some(Decimal::ZERO),
+        ..Default::default()
+    };
+
+    // Someone else learns nothing about the update, valid or not.
+    let result = program.send(BOB, Action::ModifyOrder(order.id, invalid.clone()));
+    assert_eq!(reply(&result, BOB), Err(OrderError::Unauthorized));
+
+    let result = program.send(BOB, Action::DeleteOrder(order.id));
+    assert_eq!(reply(&result, BOB), Err(OrderError::Unauthorized));
+
+    let result = program.send(BOB, Action::ModifyOrder([7; 32], invalid.clone()));
+    assert_eq!(reply(&result, BOB), Err(OrderError::NotFound));
+
+    // Nor does the owner, once the order is filled.
+    add(&program, BOB, bid("1", "100")).unwrap();
+
+    let result = program.send(ALICE, Action::ModifyOrder(order.id, invalid));
+    assert_eq!(reply(&result, ALICE), Err(OrderError::Locked));
+
+    let result = program.send(ALICE, Action::DeleteOrder(order.id));
+    assert_eq!(reply(&result, ALICE), Err(OrderError::Locked));
+}
+
+#[test]
+fn orders_of_one_sender_in_one_block_get_distinct_ids() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    // No blocks pass between the two.
+    let first = add(&program, ALICE, ask("1", "100")).unwrap();
+    let second = add(&program, ALICE, ask("1", "100")).unwrap();
+
+    assert_ne!(first.id, second.id);
+    assert_eq!(first.id, order_id(0, ActorId::from(ALICE)));
+    assert_eq!(second.id, order_id(1, ActorId::from(ALICE)));
+
+    assert!(find(&program, first.id).is_some());
+    assert!(find(&program, second.id).is_some());
+}
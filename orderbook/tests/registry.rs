@@ -0,0 +1,82 @@
+mod common;
+
+use common::*;
+use gtest::System;
+use order_book_io::{
+    Action, AssetInfo, Event, OrderError, Pair, PairInfo, StateQuery, StateReply, DECIMALS,
+};
+
+#[test]
+fn only_the_owner_registers_assets() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let info = AssetInfo {
+        decimals: 18,
+        min_size: decimal("0.001"),
+    };
+
+    // Whether the info is valid is nobody else's business.
+    for decimals in [info.decimals, DECIMALS as u8 + 1] {
+        let result = program.send(
+            ALICE,
+            Action::RegisterAsset(key("ETH"), AssetInfo { decimals, ..info }),
+        );
+
+        assert!(!result.main_failed());
+        assert_eq!(reply(&result, ALICE), Err(OrderError::NotAllowed));
+    }
+
+    let result = program.send(OWNER, Action::RegisterAsset(key("ETH"), info));
+    assert_eq!(reply(&result, OWNER), Err(OrderError::TooManyDecimals));
+
+    let info = AssetInfo {
+        decimals: DECIMALS as u8,
+        ..info
+    };
+
+    let result = program.send(OWNER, Action::RegisterAsset(key("ETH"), info));
+    assert_eq!(
+        reply(&result, OWNER),
+        Ok(Event::AssetRegistered(key("ETH")))
+    );
+
+    let StateReply::Assets(assets) = state(&program, StateQuery::Assets) else {
+        panic!("Unexpected state reply");
+    };
+    assert!(assets.contains(&(key("ETH"), info)));
+}
+
+#[test]
+fn only_the_owner_lists_pairs() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let info = PairInfo {
+        tick_size: decimal("0.1"),
+    };
+
+    for (a, b) in [(BASE, BASE), (BASE, "ETH"), ("ETH", QUOTE)] {
+        let result = program.send(ALICE, Action::ListPair(key(a), key(b), info));
+
+        assert!(!result.main_failed());
+        assert_eq!(reply(&result, ALICE), Err(OrderError::NotAllowed));
+    }
+
+    let result = program.send(OWNER, Action::ListPair(key(BASE), key(BASE), info));
+    assert_eq!(reply(&result, OWNER), Err(OrderError::SameAsset));
+
+    let result = program.send(OWNER, Action::ListPair(key(BASE), key("ETH"), info));
+    assert_eq!(reply(&result, OWNER), Err(OrderError::UnknownAsset));
+
+    // Either order of the assets lists the same pair.
+    let pair = Pair::new(key(BASE), key(QUOTE));
+
+    let result = program.send(OWNER, Action::ListPair(key(QUOTE), key(BASE), info));
+    assert_eq!(reply(&result, OWNER), Ok(Event::PairListed(pair.clone())));
+
+    assert_eq!(
+        state(&program, StateQuery::Pairs),
+        StateReply::Pairs(vec![(pair, info)])
+    );
+}
@@ -0,0 +1,258 @@
+mod common;
+
+use common::*;
+use gstd::codec::{Decode, Encode};
+use gtest::{Program, System, WasmProgram};
+use order_book_io::{
+    Action, Event, OracleAction, OracleEvent, OraclePrices, Order, OrderError, OrderId, OrderType,
+    TimeInForce, Trigger, TriggerCondition, MIN_ORDER_TTL,
+};
+use std::collections::BTreeMap;
+
+/// Stand-in for the `price_oracle` program, answering `GetPrices` with the
+/// last price set for the asset.
+#[derive(Debug, Default)]
+struct Oracle {
+    prices: BTreeMap<String, String>,
+}
+
+impl WasmProgram for Oracle {
+    fn init(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        let action =
+            OracleAction::decode(&mut &payload[..]).map_err(|_| "Failed to decode OracleAction")?;
+
+        let reply = match action {
+            OracleAction::SetPrice { name, price, .. } => {
+                self.prices.insert(name, price);
+                OracleEvent::PricesSet
+            }
+            OracleAction::GetPrices { crypto } => {
+                OracleEvent::Prices(self.prices.get(&crypto).map(|price| OraclePrices {
+                    minute: price.clone(),
+                    hourly: price.clone(),
+                    weekly: price.clone(),
+                }))
+            }
+        };
+
+        Ok(Some(reply.encode()))
+    }
+
+    fn handle_reply(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<u8>, &'static str> {
+        Ok(Vec::new())
+    }
+}
+
+/// Deploys the order book with a mock oracle behind it.
+fn deploy_with_oracle(system: &System) -> (Program<'_>, Program<'_>) {
+    let oracle = Program::mock(system, Oracle::default());
+    assert!(!oracle.send(OWNER, 0u8).main_failed());
+
+    let program = deploy(system, Some(actor(&oracle)));
+
+    (program, oracle)
+}
+
+/// Sets the oracle price of `BASE` and pokes the order book with it.
+fn poke(program: &Program, oracle: &Program, price: &str) -> Result<Vec<OrderId>, OrderError> {
+    let result = oracle.send(
+        OWNER,
+        OracleAction::SetPrice {
+            name: BASE.into(),
+            price: price.into(),
+            interval: "minute".into(),
+        },
+    );
+    assert!(!result.main_failed());
+
+    let result = program.send(CAROL, Action::Poke(key(BASE)));
+
+    match reply(&result, CAROL)? {
+        Event::StopsTriggered(triggered) => Ok(triggered),
+        event => panic!("Unexpected reply {event:?}"),
+    }
+}
+
+fn stop(mut order: Order, condition: TriggerCondition, price: &str) -> Order {
+    order.trigger = Some(Trigger {
+        asset: key(BASE),
+        price: decimal(price),
+        condition,
+    });
+    order
+}
+
+fn market(mut order: Order, slippage: &str) -> Order {
+    order.order_type = OrderType::Market;
+    order.time_in_force = TimeInForce::ImmediateOrCancel;
+    order.user_slippage = decimal(slippage);
+    order
+}
+
+#[test]
+fn stops_trigger_in_their_direction() {
+    let system = System::new();
+    let (program, oracle) = deploy_with_oracle(&system);
+
+    let bid_maker = add(&program, BOB, bid("1", "90")).unwrap();
+    let ask_maker = add(&program, BOB, ask("1", "110")).unwrap();
+
+    // Sells once the price falls to 95, buys once it rises to 105.
+    let sell = add(
+        &program,
+        ALICE,
+        stop(
+            market(ask("1", "95"), "10"),
+            TriggerCondition::AtOrBelow,
+            "95",
+        ),
+    )
+    .unwrap();
+    let buy = add(
+        &program,
+        ALICE,
+        stop(bid("1", "110"), TriggerCondition::AtOrAbove, "105"),
+    )
+    .unwrap();
+
+    // Stop orders wait outside the book.
+    for order in [&sell, &buy] {
+        assert!(order.is_inactive && order.inactive_time_start > 0);
+        assert!(trades(&program, order.id).is_empty());
+    }
+
+    assert_eq!(poke(&program, &oracle, "100"), Ok(vec![]));
+
+    assert_eq!(poke(&program, &oracle, "95"), Ok(vec![sell.id]));
+    assert_eq!(
+        trades(&program, sell.id),
+        [(bid_maker.id, decimal("90"), decimal("1"))]
+    );
+    assert!(find(&program, buy.id).unwrap().is_inactive);
+
+    assert_eq!(poke(&program, &oracle, "104.99"), Ok(vec![]));
+
+    assert_eq!(poke(&program, &oracle, "105"), Ok(vec![buy.id]));
+    assert_eq!(
+        trades(&program, buy.id),
+        [(ask_maker.id, decimal("110"), decimal("1"))]
+    );
+
+    // A trigger fires once.
+    assert_eq!(poke(&program, &oracle, "80"), Ok(vec![]));
+}
+
+#[test]
+fn triggered_stops_become_their_order_type() {
+    let system = System::new();
+    let (program, oracle) = deploy_with_oracle(&system);
+
+    let stop_limit = add(
+        &program,
+        ALICE,
+        stop(ask("1", "100"), TriggerCondition::AtOrBelow, "95"),
+    )
+    .unwrap();
+    let stop_market = add(
+        &program,
+        ALICE,
+        stop(
+            market(ask("1", "100"), "5"),
+            TriggerCondition::AtOrBelow,
+            "95",
+        ),
+    )
+    .unwrap();
+
+    let result = program.send(CAROL, Action::Poke(key(BASE)));
+    assert_eq!(reply(&result, CAROL), Err(OrderError::NoPrice));
+
+    let result = oracle.send(
+        OWNER,
+        OracleAction::SetPrice {
+            name: BASE.into(),
+            price: "95".into(),
+            interval: "minute".into(),
+        },
+    );
+    assert!(!result.main_failed());
+
+    let result = program.send(CAROL, Action::Poke(key(BASE)));
+    assert_eq!(
+        reply(&result, CAROL),
+        Ok(Event::StopsTriggered(vec![stop_limit.id, stop_market.id]))
+    );
+
+    let alice = events(&result, ALICE);
+    assert!(alice.contains(&Event::OrderTriggered(stop_limit.id)));
+    assert!(alice.contains(&Event::OrderTriggered(stop_market.id)));
+
+    // With nothing to trade with, the stop-limit order rests as a limit
+    // order and the stop order is cancelled.
+    assert!(alice.contains(&Event::OrderCancelled(stop_market.id)));
+    assert_eq!(find(&program, stop_market.id), None);
+
+    let limit = find(&program, stop_limit.id).unwrap();
+    assert!(!limit.is_inactive && limit.trigger.is_none());
+    assert_eq!(limit.inactive_time_start, 0);
+
+    let taker = add(&program, BOB, bid("1", "100")).unwrap();
+    assert_eq!(
+        trades(&program, taker.id),
+        [(stop_limit.id, decimal("100"), decimal("1"))]
+    );
+}
+
+#[test]
+fn expired_stops_never_trigger() {
+    let system = System::new();
+    let (program, oracle) = deploy_with_oracle(&system);
+
+    let maker = add(&program, BOB, bid("1", "90")).unwrap();
+
+    let result = program.send(
+        ALICE,
+        Action::AddOrder(
+            stop(
+                market(ask("1", "95"), "10"),
+                TriggerCondition::AtOrBelow,
+                "95",
+            ),
+            Some(MIN_ORDER_TTL),
+        ),
+    );
+    let expiring = added(&result, ALICE).unwrap();
+
+    while system.block_timestamp() < expiring.valid_until {
+        system.spend_blocks(1);
+    }
+
+    assert_eq!(poke(&program, &oracle, "90"), Ok(vec![]));
+    assert!(trades(&program, expiring.id).is_empty());
+    assert!(trades(&program, maker.id).is_empty());
+}
+
+#[test]
+fn pokes_need_an_oracle() {
+    let system = System::new();
+    let program = deploy(&system, None);
+
+    let result = program.send(CAROL, Action::Poke(key(BASE)));
+    assert_eq!(reply(&result, CAROL), Err(OrderError::NoOracle));
+
+    // Nor can anyone but the oracle push prices.
+    let result = program.send(CAROL, Action::PriceUpdate(key(BASE), decimal("100")));
+    assert_eq!(reply(&result, CAROL), Err(OrderError::NotAllowed));
+}